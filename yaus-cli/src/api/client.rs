@@ -3,22 +3,25 @@ use super::{
     HTTP_USER_AGENT,
 };
 use reqwest::{Method, StatusCode, Url};
-use serde::Serialize;
 
 pub struct Client<'client> {
     pub client: reqwest::Client,
-    pub user: User<'client>,
+    pub credentials: Credentials<'client>,
     pub url: Url,
 }
 
-#[derive(Serialize)]
-pub struct User<'user> {
-    pub username: &'user str,
-    pub password: &'user str,
+/// How the client authenticates itself against the server
+pub enum Credentials<'credentials> {
+    Password {
+        username: &'credentials str,
+        password: &'credentials str,
+    },
+    /// An API token, for example one created by `yaus login`
+    Token(&'credentials str),
 }
 
 impl<'client> Client<'client> {
-    pub async fn new(raw_url: &str, user: User<'client>) -> Result<Client<'client>> {
+    pub async fn new(raw_url: &str, credentials: Credentials<'client>) -> Result<Client<'client>> {
         // Parse the source url into an URL struct
         let url = Url::parse(raw_url)?;
 
@@ -26,16 +29,20 @@ impl<'client> Client<'client> {
         let client = reqwest::Client::builder()
             .user_agent(HTTP_USER_AGENT)
             .build()?;
+        let client = Self {
+            client,
+            credentials,
+            url,
+        };
 
         // Attempt to authenticate using the provided credentials
         let response = client
-            .request(Method::GET, url.join("/api/auth")?)
-            .query(&[("username", user.username), ("password", user.password)])
-            .send()
+            .client
+            .execute(client.build_request::<()>(Method::GET, "/api/auth", None)?)
             .await?;
 
         match response.status() {
-            StatusCode::OK => Ok(Self { client, user, url }),
+            StatusCode::OK => Ok(client),
             status => Err(Error::Yaus(status)),
         }
     }
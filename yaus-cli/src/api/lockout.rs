@@ -0,0 +1,49 @@
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+
+use super::client::Client;
+use super::errors::{Error, Result};
+
+/// A client or a user whose failed authentication attempts are tracked by the server
+#[derive(Deserialize, Debug)]
+pub struct Lockout {
+    pub ip: Option<String>,
+    pub username: Option<String>,
+    pub failures: u32,
+    /// Whether the client or user is locked out instead of just being delayed
+    pub locked: bool,
+    /// How many seconds remain until further attempts are accepted
+    pub retry_after: u64,
+}
+
+impl Client<'_> {
+    pub async fn list_lockouts(&self) -> Result<Vec<Lockout>> {
+        let result = self
+            .client
+            .execute(self.build_request::<()>(Method::GET, "/api/lockouts", None)?)
+            .await?;
+        match result.status() {
+            StatusCode::OK => Ok(result.json().await?),
+            status => Err(Error::Yaus(status)),
+        }
+    }
+
+    /// Clears the lockouts of a client IP or a user, or all of them if neither is specified
+    pub async fn clear_lockouts(&self, ip: Option<&str>, username: Option<&str>) -> Result<()> {
+        let mut request = self.build_request::<()>(Method::DELETE, "/api/lockouts", None)?;
+        {
+            let mut pairs = request.url_mut().query_pairs_mut();
+            if let Some(ip) = ip {
+                pairs.append_pair("ip", ip);
+            }
+            if let Some(username) = username {
+                pairs.append_pair("username", username);
+            }
+        }
+        let result = self.client.execute(request).await?;
+        match result.status() {
+            StatusCode::OK => Ok(()),
+            status => Err(Error::Yaus(status)),
+        }
+    }
+}
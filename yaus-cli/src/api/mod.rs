@@ -1,10 +1,17 @@
 mod client;
 mod errors;
+mod lockout;
 mod request;
+mod stats;
+mod tag;
+mod token;
+mod user;
 mod yaus;
 
-pub use client::{Client, User};
+pub use client::{Client, Credentials};
 pub use errors::{Error, Result};
-pub use yaus::Redirect;
+pub use token::NewToken;
+pub use user::NewAccount;
+pub use yaus::{ListQuery, NewRedirect, RedirectUpdate};
 
 const HTTP_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"),);
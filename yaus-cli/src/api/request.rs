@@ -1,7 +1,10 @@
 use reqwest::{Method, Request};
 use serde::Serialize;
 
-use super::{client::Client, errors::Result};
+use super::{
+    client::{Client, Credentials},
+    errors::Result,
+};
 
 impl Client<'_> {
     pub fn build_request<Body: Serialize>(
@@ -10,11 +13,14 @@ impl Client<'_> {
         path: &str,
         body: Option<Body>,
     ) -> Result<Request> {
-        // Create a request
-        let request = self.client.request(method, self.url.join(path)?).query(&[
-            ("username", self.user.username),
-            ("password", self.user.password),
-        ]);
+        // Create an authenticated request
+        let request = self.client.request(method, self.url.join(path)?);
+        let request = match self.credentials {
+            Credentials::Password { username, password } => {
+                request.basic_auth(username, Some(password))
+            }
+            Credentials::Token(token) => request.bearer_auth(token),
+        };
         // Append a body if needed
         match body {
             Some(body) => Ok(request.json(&body).build()?),
@@ -0,0 +1,49 @@
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+
+use super::client::Client;
+use super::errors::{Error, Result};
+
+#[derive(Deserialize, Debug)]
+pub struct Visit {
+    pub visited_at: String,
+    pub referrer: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UrlStats {
+    pub total: i64,
+    pub visits: Vec<Visit>,
+}
+
+impl Client<'_> {
+    pub async fn get_stats(
+        &self,
+        short_id: &str,
+        domain: Option<&str>,
+        limit: u32,
+    ) -> Result<UrlStats> {
+        let result = self
+            .client
+            .execute(
+                self.build_request::<()>(
+                    Method::GET,
+                    {
+                        let mut url = self.redirect_url(short_id, "/stats", domain);
+                        url.query_pairs_mut()
+                            .append_pair("limit", &limit.to_string());
+                        url
+                    }
+                    .as_str(),
+                    None,
+                )?,
+            )
+            .await?;
+        match result.status() {
+            StatusCode::OK => Ok(result.json().await?),
+            status => Err(Error::Yaus(status)),
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+
+use super::client::Client;
+use super::errors::{Error, Result};
+
+#[derive(Deserialize, Debug)]
+pub struct Tag {
+    pub tag: String,
+    /// How many redirects carry this tag
+    pub urls: i64,
+}
+
+impl Client<'_> {
+    pub async fn list_tags(&self) -> Result<Vec<Tag>> {
+        let result = self
+            .client
+            .execute(self.build_request::<()>(Method::GET, "/api/tags", None)?)
+            .await?;
+        match result.status() {
+            StatusCode::OK => Ok(result.json().await?),
+            status => Err(Error::Yaus(status)),
+        }
+    }
+}
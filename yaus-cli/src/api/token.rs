@@ -0,0 +1,72 @@
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use super::client::Client;
+use super::errors::{Error, Result};
+
+#[derive(Deserialize, Debug)]
+pub struct ApiToken {
+    pub id: i32,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<String>,
+    pub created_at: String,
+    pub last_used_at: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NewToken<'token> {
+    pub name: &'token str,
+    /// If omitted, the token receives all scopes of its user
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scopes: Option<&'token [String]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<&'token str>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CreatedToken {
+    /// The token itself, the server does not reveal it again
+    pub token: String,
+    #[serde(flatten)]
+    pub details: ApiToken,
+}
+
+impl Client<'_> {
+    pub async fn list_tokens(&self) -> Result<Vec<ApiToken>> {
+        let result = self
+            .client
+            .execute(self.build_request::<()>(Method::GET, "/api/tokens", None)?)
+            .await?;
+        match result.status() {
+            StatusCode::OK => Ok(result.json().await?),
+            status => Err(Error::Yaus(status)),
+        }
+    }
+
+    pub async fn create_token(&self, token: &NewToken<'_>) -> Result<CreatedToken> {
+        let result = self
+            .client
+            .execute(self.build_request(Method::POST, "/api/tokens", Some(token))?)
+            .await?;
+        match result.status() {
+            StatusCode::OK => Ok(result.json().await?),
+            status => Err(Error::Yaus(status)),
+        }
+    }
+
+    pub async fn delete_token(&self, id: i32) -> Result<()> {
+        let result = self
+            .client
+            .execute(self.build_request::<()>(
+                Method::DELETE,
+                &format!("/api/tokens/{id}"),
+                None,
+            )?)
+            .await?;
+        match result.status() {
+            StatusCode::OK => Ok(()),
+            status => Err(Error::Yaus(status)),
+        }
+    }
+}
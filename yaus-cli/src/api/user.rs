@@ -0,0 +1,85 @@
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+
+use super::client::Client;
+use super::errors::{Error, Result};
+
+/// A user account on the Yaus server
+#[derive(Deserialize, Debug)]
+pub struct Account {
+    pub username: String,
+    /// Either `viewer`, `editor` or `admin`
+    pub role: String,
+    pub created_at: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NewAccount<'account> {
+    pub username: &'account str,
+    pub password: &'account str,
+    pub role: &'account str,
+}
+
+#[derive(Serialize, Debug)]
+struct PasswordUpdate<'update> {
+    password: &'update str,
+}
+
+impl Client<'_> {
+    fn user_path(&self, username: &str) -> String {
+        let mut url = self.url.clone();
+        url.path_segments_mut()
+            .expect("A client can only exist with a valid base-URL")
+            .clear()
+            .extend(["api", "users", username]);
+        url.to_string()
+    }
+
+    pub async fn list_users(&self) -> Result<Vec<Account>> {
+        let result = self
+            .client
+            .execute(self.build_request::<()>(Method::GET, "/api/users", None)?)
+            .await?;
+        match result.status() {
+            StatusCode::OK => Ok(result.json().await?),
+            status => Err(Error::Yaus(status)),
+        }
+    }
+
+    pub async fn create_user(&self, account: &NewAccount<'_>) -> Result<Account> {
+        let result = self
+            .client
+            .execute(self.build_request(Method::POST, "/api/users", Some(account))?)
+            .await?;
+        match result.status() {
+            StatusCode::OK => Ok(result.json().await?),
+            status => Err(Error::Yaus(status)),
+        }
+    }
+
+    pub async fn change_password(&self, username: &str, password: &str) -> Result<()> {
+        let result = self
+            .client
+            .execute(self.build_request(
+                Method::PATCH,
+                &self.user_path(username),
+                Some(PasswordUpdate { password }),
+            )?)
+            .await?;
+        match result.status() {
+            StatusCode::OK => Ok(()),
+            status => Err(Error::Yaus(status)),
+        }
+    }
+
+    pub async fn delete_user(&self, username: &str) -> Result<()> {
+        let result = self
+            .client
+            .execute(self.build_request::<()>(Method::DELETE, &self.user_path(username), None)?)
+            .await?;
+        match result.status() {
+            StatusCode::OK => Ok(()),
+            status => Err(Error::Yaus(status)),
+        }
+    }
+}
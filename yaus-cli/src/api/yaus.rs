@@ -1,4 +1,4 @@
-use reqwest::{Method, StatusCode};
+use reqwest::{Method, StatusCode, Url};
 use serde::{Deserialize, Serialize};
 
 use super::client::Client;
@@ -6,17 +6,170 @@ use super::errors::{Error, Result};
 
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Redirect {
+    /// Is empty for redirects of the default domain
+    #[serde(default)]
+    pub domain: String,
     pub short: String,
     pub target_url: String,
+    pub expires_at: Option<String>,
+    pub fallback_url: Option<String>,
+    pub max_visits: Option<i32>,
+    pub created_at: String,
+    pub updated_at: String,
+    pub created_by: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    /// Is set while the target domain of the redirect is blocked
+    #[serde(default)]
+    pub disabled_reason: Option<String>,
+    /// Is omitted if the redirect uses the server-wide default
+    #[serde(default)]
+    pub redirect_type: Option<String>,
+    /// Whether the path following the short id is appended to the target
+    #[serde(default)]
+    pub forward_path: bool,
+    /// Whether the query string of visits is merged into the one of the target
+    #[serde(default)]
+    pub forward_query: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RedirectPage {
+    pub urls: Vec<Redirect>,
+    /// Is omitted on the last page
+    pub next_cursor: Option<String>,
+    /// The amount of redirects matching the filter across all pages
+    pub total: i64,
+}
+
+/// Restricts and orders the redirects returned by the server
+pub struct ListQuery<'query> {
+    /// Only redirects of this domain are returned, omit it for the default domain
+    pub domain: Option<&'query str>,
+    /// Either `short` or `created`
+    pub sort: &'query str,
+    pub prefix: Option<&'query str>,
+    /// Only redirects whose target URL contains this text are returned
+    pub search: Option<&'query str>,
+    pub tag: Option<&'query str>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NewRedirect {
+    /// If omitted, the redirect is created for the default domain
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    /// If omitted, the server generates a short id
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short: Option<String>,
+    pub target_url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_visits: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redirect_type: Option<String>,
+    pub forward_path: bool,
+    pub forward_query: bool,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct RedirectUpdate {
+    /// Renames the redirect
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Replaces all tags of the redirect
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    /// Attached besides the current tags of the redirect
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub add_tags: Option<Vec<String>>,
+    /// Detached while leaving all other tags of the redirect untouched
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remove_tags: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redirect_type: Option<String>,
 }
 
 impl Client<'_> {
-    pub async fn list_urls(&self, max_entries: u32) -> Result<Vec<Redirect>> {
+    /// Builds the API URL of a redirect, which is identified by its domain and short id
+    pub(super) fn redirect_url(&self, short_id: &str, suffix: &str, domain: Option<&str>) -> Url {
+        let mut url = self.url.clone();
+        url.set_path(&format!("/api/url/{short_id}{suffix}"));
+        if let Some(domain) = domain {
+            url.query_pairs_mut().append_pair("domain", domain);
+        }
+        url
+    }
+
+    /// Returns the URL visitors use to follow a redirect
+    pub fn source_url(&self, redirect: &Redirect) -> Url {
+        let mut url = self
+            .url
+            .join(&redirect.short)
+            .expect("A client can only exist with a valid base-URL");
+        if !redirect.domain.is_empty() && url.set_host(Some(&redirect.domain)).is_err() {
+            return self.url.clone();
+        }
+        url
+    }
+
+    pub async fn list_urls(
+        &self,
+        query: &ListQuery<'_>,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<RedirectPage> {
+        let mut request = self.build_request::<()>(Method::GET, "/api/urls", None)?;
+        {
+            let mut pairs = request.url_mut().query_pairs_mut();
+            pairs
+                .append_pair("sort", query.sort)
+                .append_pair("limit", &limit.to_string());
+            if let Some(domain) = query.domain {
+                pairs.append_pair("domain", domain);
+            }
+            if let Some(prefix) = query.prefix {
+                pairs.append_pair("prefix", prefix);
+            }
+            if let Some(search) = query.search {
+                pairs.append_pair("q", search);
+            }
+            if let Some(tag) = query.tag {
+                pairs.append_pair("tag", tag);
+            }
+            if let Some(cursor) = cursor {
+                pairs.append_pair("cursor", cursor);
+            }
+        }
+        let result = self.client.execute(request).await?;
+        match result.status() {
+            StatusCode::OK => Ok(result.json().await?),
+            status => Err(Error::Yaus(status)),
+        }
+    }
+
+    pub async fn get_target(&self, short_id: &str, domain: Option<&str>) -> Result<Redirect> {
         let result = self
             .client
             .execute(self.build_request::<()>(
                 Method::GET,
-                &format!("/api/urls/{max_entries}"),
+                self.redirect_url(short_id, "", domain).as_str(),
                 None,
             )?)
             .await?;
@@ -26,21 +179,14 @@ impl Client<'_> {
         }
     }
 
-    pub async fn get_target(&self, short_id: &str) -> Result<Redirect> {
+    pub async fn create_url(&self, redirect: &NewRedirect) -> Result<Redirect> {
         let result = self
             .client
-            .execute(
-                self.build_request::<()>(
-                    Method::GET,
-                    {
-                        let mut url = self.url.clone();
-                        url.set_path(&format!("/api/url/{short_id}"));
-                        url
-                    }
-                    .as_str(),
-                    None,
-                )?,
-            )
+            .execute(self.build_request::<&NewRedirect>(
+                Method::POST,
+                "/api/url",
+                Some(redirect),
+            )?)
             .await?;
         match result.status() {
             StatusCode::OK => Ok(result.json().await?),
@@ -48,32 +194,34 @@ impl Client<'_> {
         }
     }
 
-    pub async fn create_url(&self, redirect: &Redirect) -> Result<()> {
+    pub async fn update_url(
+        &self,
+        short_id: &str,
+        domain: Option<&str>,
+        update: &RedirectUpdate,
+    ) -> Result<Redirect> {
         let result = self
             .client
-            .execute(self.build_request::<&Redirect>(Method::POST, "/api/url", Some(redirect))?)
+            .execute(self.build_request::<&RedirectUpdate>(
+                Method::PATCH,
+                self.redirect_url(short_id, "", domain).as_str(),
+                Some(update),
+            )?)
             .await?;
         match result.status() {
-            StatusCode::OK => Ok(()),
+            StatusCode::OK => Ok(result.json().await?),
             status => Err(Error::Yaus(status)),
         }
     }
 
-    pub async fn delete_url(&self, short_id: &str) -> Result<()> {
+    pub async fn delete_url(&self, short_id: &str, domain: Option<&str>) -> Result<()> {
         let result = self
             .client
-            .execute(
-                self.build_request::<&Redirect>(
-                    Method::DELETE,
-                    {
-                        let mut url = self.url.clone();
-                        url.set_path(&format!("/api/url/{short_id}"));
-                        url
-                    }
-                    .as_str(),
-                    None,
-                )?,
-            )
+            .execute(self.build_request::<&Redirect>(
+                Method::DELETE,
+                self.redirect_url(short_id, "", domain).as_str(),
+                None,
+            )?)
             .await?;
         match result.status() {
             StatusCode::OK => Ok(()),
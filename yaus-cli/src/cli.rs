@@ -1,25 +1,59 @@
 use crate::api::Client;
-use crate::api::Redirect;
+use crate::api::ListQuery;
+use crate::api::NewAccount;
+use crate::api::NewRedirect;
+use crate::api::NewToken;
+use crate::api::RedirectUpdate;
 use crate::api::Result;
 use cli_table::CellStruct;
 use cli_table::{Cell, Style, Table};
 
-pub async fn create_redirect(client: &Client<'_>, redirect: &Redirect) -> Result<()> {
+pub async fn create_redirect(client: &Client<'_>, redirect: &NewRedirect) -> Result<()> {
     println!("Creating redirect...",);
-    if let Err(err) = client.create_url(redirect).await {
-        eprintln!("Could not create redirect: {:?}", err);
-        return Err(err);
+    let redirect = match client.create_url(redirect).await {
+        Ok(redirect) => redirect,
+        Err(err) => {
+            eprintln!("Could not create redirect: {:?}", err);
+            return Err(err);
+        }
     };
     println!(
         "Successfully created redirect from {} -> {}",
-        redirect.short, redirect.target_url
+        client.source_url(&redirect),
+        redirect.target_url
     );
     Ok(())
 }
 
-pub async fn delete_redirect(client: &Client<'_>, short_id: &str) -> Result<()> {
+pub async fn update_redirect(
+    client: &Client<'_>,
+    short_id: &str,
+    domain: Option<&str>,
+    update: &RedirectUpdate,
+) -> Result<()> {
+    println!("Updating redirect `{short_id}`...",);
+    let redirect = match client.update_url(short_id, domain, update).await {
+        Ok(redirect) => redirect,
+        Err(err) => {
+            eprintln!("Could not update redirect: {:?}", err);
+            return Err(err);
+        }
+    };
+    println!(
+        "Successfully updated redirect {} -> {}",
+        client.source_url(&redirect),
+        redirect.target_url
+    );
+    Ok(())
+}
+
+pub async fn delete_redirect(
+    client: &Client<'_>,
+    short_id: &str,
+    domain: Option<&str>,
+) -> Result<()> {
     println!("Deleting redirect `{short_id}`...",);
-    if let Err(err) = client.delete_url(short_id).await {
+    if let Err(err) = client.delete_url(short_id, domain).await {
         eprintln!("Could not delete redirect: {:?}", err);
         return Err(err);
     };
@@ -27,29 +61,112 @@ pub async fn delete_redirect(client: &Client<'_>, short_id: &str) -> Result<()>
     Ok(())
 }
 
-pub async fn get_target(client: &Client<'_>, short_id: &str) -> Result<()> {
-    match client.get_target(short_id).await {
-        Ok(redirect) => {
-            println!("Redirect {short_id}\n=> {}", redirect.target_url);
-            Ok(())
-        }
+pub async fn get_target(
+    client: &Client<'_>,
+    short_id: &str,
+    domain: Option<&str>,
+    verbose: bool,
+) -> Result<()> {
+    let redirect = match client.get_target(short_id, domain).await {
+        Ok(redirect) => redirect,
         Err(err) => {
             eprintln!("Could not get target of redirect: {:?}", err);
-            Err(err)
+            return Err(err);
         }
+    };
+    if !verbose {
+        println!("Redirect {short_id}\n=> {}", redirect.target_url);
+        if let Some(disabled_reason) = redirect.disabled_reason {
+            println!("Disabled, {disabled_reason}");
+        }
+        if let Some(expires_at) = redirect.expires_at {
+            println!("Expires at {expires_at}");
+        }
+        if let Some(max_visits) = redirect.max_visits {
+            println!("{max_visits} visit(s) left");
+        }
+        if let Some(fallback_url) = redirect.fallback_url {
+            println!("Falls back to {fallback_url}");
+        }
+        return Ok(());
     }
+    let forwarded = [
+        ("path", redirect.forward_path),
+        ("query", redirect.forward_query),
+    ]
+    .into_iter()
+    .filter_map(|(part, forwarded)| forwarded.then_some(part))
+    .collect::<Vec<_>>();
+    let table = vec![
+        ("Source URL", Some(client.source_url(&redirect).to_string())),
+        ("Short id", Some(redirect.short)),
+        ("Target URL", Some(redirect.target_url)),
+        ("Title", redirect.title),
+        ("Description", redirect.description),
+        (
+            "Tags",
+            (!redirect.tags.is_empty()).then(|| redirect.tags.join(", ")),
+        ),
+        ("Created at", Some(redirect.created_at)),
+        ("Created by", redirect.created_by),
+        ("Updated at", Some(redirect.updated_at)),
+        ("Expires at", redirect.expires_at),
+        (
+            "Visits left",
+            redirect.max_visits.map(|max| max.to_string()),
+        ),
+        ("Fallback URL", redirect.fallback_url),
+        ("Redirect type", redirect.redirect_type),
+        (
+            "Forwarded",
+            (!forwarded.is_empty()).then(|| forwarded.join(", ")),
+        ),
+        ("Disabled", redirect.disabled_reason),
+    ]
+    .into_iter()
+    .map(|(field, value)| {
+        vec![
+            field.cell().bold(true),
+            value.unwrap_or_else(|| "-".to_string()).cell(),
+        ]
+    })
+    .collect::<Vec<Vec<CellStruct>>>()
+    .table();
+    println!("{}", table.display().unwrap());
+    Ok(())
 }
 
-pub async fn list_redirects(client: &Client<'_>, max_entries: u32) -> Result<()> {
-    let redirects = match client.list_urls(max_entries).await {
-        Ok(response) => response,
-        Err(err) => {
-            eprintln!("Could not list all redirects: {:?}", err);
-            return Err(err);
-        }
-    };
+/// How many redirects are requested from the server at once
+const PAGE_SIZE: u32 = 1000;
+
+pub async fn list_redirects(
+    client: &Client<'_>,
+    query: &ListQuery<'_>,
+    max_entries: u32,
+) -> Result<()> {
+    // Fetch pages until the server has no more redirects or enough have been collected
+    let mut redirects = vec![];
+    let mut cursor = None;
+    let mut total = 0;
+    while (redirects.len() as u32) < max_entries {
+        let limit = PAGE_SIZE.min(max_entries - redirects.len() as u32);
+        let page = match client.list_urls(query, cursor.as_deref(), limit).await {
+            Ok(response) => response,
+            Err(err) => {
+                eprintln!("Could not list all redirects: {:?}", err);
+                return Err(err);
+            }
+        };
+        redirects.extend(page.urls);
+        total = page.total;
+        cursor = match page.next_cursor {
+            Some(cursor) => Some(cursor),
+            None => break,
+        };
+    }
+    let shown = redirects.len();
     let output = match &redirects.len() {
-        0 => format!("No redirects (empty set)"),
+        0 => "No redirects (empty set)".to_string(),
         _ => {
             let table = redirects
                 .into_iter()
@@ -57,22 +174,368 @@ pub async fn list_redirects(client: &Client<'_>, max_entries: u32) -> Result<()>
                 .map(|(index, redirect)| {
                     vec![
                         index.cell().dimmed(true),
-                        client
-                            .url
-                            .join(&redirect.short)
-                            .expect("A client can only exist with a valid base-URL")
-                            .cell(),
+                        client.source_url(&redirect).cell(),
                         redirect.target_url.cell(),
+                        redirect.tags.join(", ").cell(),
                     ]
                 })
                 .collect::<Vec<Vec<CellStruct>>>()
                 .table()
-                .title(vec!["".cell(), "Source URL".cell(), "Target URL".cell()])
+                .title(vec![
+                    "".cell(),
+                    "Source URL".cell(),
+                    "Target URL".cell(),
+                    "Tags".cell(),
+                ])
                 .bold(true);
 
             table.display().unwrap().to_string()
         }
     };
     println!("{output}");
+    if (shown as i64) < total {
+        println!("Showing {shown} of {total} redirects");
+    }
+    Ok(())
+}
+
+pub async fn show_stats(
+    client: &Client<'_>,
+    short_id: &str,
+    domain: Option<&str>,
+    last: u32,
+) -> Result<()> {
+    let stats = match client.get_stats(short_id, domain, last).await {
+        Ok(stats) => stats,
+        Err(err) => {
+            eprintln!("Could not get statistics of redirect: {:?}", err);
+            return Err(err);
+        }
+    };
+    println!("Redirect {short_id} was visited {} time(s)", stats.total);
+    if stats.visits.is_empty() {
+        return Ok(());
+    }
+    let table = stats
+        .visits
+        .into_iter()
+        .map(|visit| {
+            vec![
+                visit.visited_at.cell(),
+                visit.ip.unwrap_or_default().cell(),
+                visit.referrer.unwrap_or_default().cell(),
+                visit.user_agent.unwrap_or_default().cell(),
+            ]
+        })
+        .collect::<Vec<Vec<CellStruct>>>()
+        .table()
+        .title(vec![
+            "Time".cell(),
+            "IP".cell(),
+            "Referrer".cell(),
+            "User agent".cell(),
+        ])
+        .bold(true);
+    println!("{}", table.display().unwrap());
+    Ok(())
+}
+
+/// Changes the tags of a redirect while leaving all other tags untouched
+/// The server applies the change, so that concurrent changes do not overwrite each other
+async fn change_tags(
+    client: &Client<'_>,
+    short_id: &str,
+    domain: Option<&str>,
+    update: &RedirectUpdate,
+) -> Result<Vec<String>> {
+    let redirect = client.update_url(short_id, domain, update).await?;
+    Ok(redirect.tags)
+}
+
+pub async fn add_tags(
+    client: &Client<'_>,
+    short_id: &str,
+    domain: Option<&str>,
+    tags: &[String],
+) -> Result<()> {
+    let update = RedirectUpdate {
+        add_tags: Some(tags.to_vec()),
+        ..Default::default()
+    };
+    match change_tags(client, short_id, domain, &update).await {
+        Ok(tags) => {
+            println!(
+                "Redirect {short_id} is now tagged with: {}",
+                tags.join(", ")
+            );
+            Ok(())
+        }
+        Err(err) => {
+            eprintln!("Could not add tags to redirect: {:?}", err);
+            Err(err)
+        }
+    }
+}
+
+pub async fn remove_tags(
+    client: &Client<'_>,
+    short_id: &str,
+    domain: Option<&str>,
+    tags: &[String],
+) -> Result<()> {
+    let update = RedirectUpdate {
+        remove_tags: Some(tags.to_vec()),
+        ..Default::default()
+    };
+    match change_tags(client, short_id, domain, &update).await {
+        Ok(tags) => {
+            println!(
+                "Redirect {short_id} is now tagged with: {}",
+                tags.join(", ")
+            );
+            Ok(())
+        }
+        Err(err) => {
+            eprintln!("Could not remove tags from redirect: {:?}", err);
+            Err(err)
+        }
+    }
+}
+
+pub async fn list_tags(client: &Client<'_>) -> Result<()> {
+    let tags = match client.list_tags().await {
+        Ok(tags) => tags,
+        Err(err) => {
+            eprintln!("Could not list tags: {:?}", err);
+            return Err(err);
+        }
+    };
+    if tags.is_empty() {
+        println!("No tags (empty set)");
+        return Ok(());
+    }
+    let table = tags
+        .into_iter()
+        .map(|tag| vec![tag.tag.cell(), tag.urls.cell()])
+        .collect::<Vec<Vec<CellStruct>>>()
+        .table()
+        .title(vec!["Tag".cell(), "Redirects".cell()])
+        .bold(true);
+    println!("{}", table.display().unwrap());
+    Ok(())
+}
+
+/// Asks for a new password twice without echoing it
+pub fn prompt_new_password() -> Option<String> {
+    let password = rpassword::read_password_from_tty(Some("New password: ")).ok()?;
+    let confirmation = rpassword::read_password_from_tty(Some("Repeat password: ")).ok()?;
+    if password != confirmation {
+        eprintln!("The passwords do not match");
+        return None;
+    }
+    Some(password)
+}
+
+pub async fn list_users(client: &Client<'_>) -> Result<()> {
+    let users = match client.list_users().await {
+        Ok(users) => users,
+        Err(err) => {
+            eprintln!("Could not list users: {:?}", err);
+            return Err(err);
+        }
+    };
+    let table = users
+        .into_iter()
+        .map(|user| {
+            vec![
+                user.username.cell(),
+                user.role.cell(),
+                user.created_at.cell(),
+            ]
+        })
+        .collect::<Vec<Vec<CellStruct>>>()
+        .table()
+        .title(vec!["Username".cell(), "Role".cell(), "Created at".cell()])
+        .bold(true);
+    println!("{}", table.display().unwrap());
+    Ok(())
+}
+
+pub async fn create_user(
+    client: &Client<'_>,
+    username: &str,
+    password: &str,
+    role: &str,
+) -> Result<()> {
+    println!("Creating user `{username}`...");
+    if let Err(err) = client
+        .create_user(&NewAccount {
+            username,
+            password,
+            role,
+        })
+        .await
+    {
+        eprintln!("Could not create user: {:?}", err);
+        return Err(err);
+    }
+    println!("Successfully created user `{username}`");
+    Ok(())
+}
+
+pub async fn change_password(client: &Client<'_>, username: &str, password: &str) -> Result<()> {
+    if let Err(err) = client.change_password(username, password).await {
+        eprintln!("Could not change password: {:?}", err);
+        return Err(err);
+    }
+    println!("Successfully changed the password of `{username}`");
+    Ok(())
+}
+
+pub async fn delete_user(client: &Client<'_>, username: &str) -> Result<()> {
+    println!("Deleting user `{username}`...");
+    if let Err(err) = client.delete_user(username).await {
+        eprintln!("Could not delete user: {:?}", err);
+        return Err(err);
+    }
+    println!("Successfully deleted user `{username}`");
+    Ok(())
+}
+
+/// Exchanges the password for an API token and returns the token
+pub async fn login(client: &Client<'_>) -> Result<String> {
+    println!("Creating API token...");
+    match client
+        .create_token(&NewToken {
+            name: "yaus-cli",
+            scopes: None,
+            expires_at: None,
+        })
+        .await
+    {
+        Ok(created) => Ok(created.token),
+        Err(err) => {
+            eprintln!("Could not create API token: {:?}", err);
+            Err(err)
+        }
+    }
+}
+
+pub async fn list_tokens(client: &Client<'_>) -> Result<()> {
+    let tokens = match client.list_tokens().await {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            eprintln!("Could not list API tokens: {:?}", err);
+            return Err(err);
+        }
+    };
+    if tokens.is_empty() {
+        println!("No API tokens (empty set)");
+        return Ok(());
+    }
+    let table = tokens
+        .into_iter()
+        .map(|token| {
+            vec![
+                token.id.cell(),
+                token.name.cell(),
+                token.scopes.join(", ").cell(),
+                token.created_at.cell(),
+                token.expires_at.unwrap_or_else(|| "-".to_string()).cell(),
+                token.last_used_at.unwrap_or_else(|| "-".to_string()).cell(),
+            ]
+        })
+        .collect::<Vec<Vec<CellStruct>>>()
+        .table()
+        .title(vec![
+            "Id".cell(),
+            "Name".cell(),
+            "Scopes".cell(),
+            "Created at".cell(),
+            "Expires at".cell(),
+            "Last used at".cell(),
+        ])
+        .bold(true);
+    println!("{}", table.display().unwrap());
+    Ok(())
+}
+
+pub async fn create_token(client: &Client<'_>, token: &NewToken<'_>) -> Result<()> {
+    let created = match client.create_token(token).await {
+        Ok(created) => created,
+        Err(err) => {
+            eprintln!("Could not create API token: {:?}", err);
+            return Err(err);
+        }
+    };
+    println!(
+        "Successfully created API token {} with scopes {}, it will not be shown again:\n{}",
+        created.details.id,
+        created.details.scopes.join(", "),
+        created.token
+    );
+    Ok(())
+}
+
+pub async fn delete_token(client: &Client<'_>, id: i32) -> Result<()> {
+    println!("Deleting API token {id}...");
+    if let Err(err) = client.delete_token(id).await {
+        eprintln!("Could not delete API token: {:?}", err);
+        return Err(err);
+    }
+    println!("Successfully deleted API token {id}");
+    Ok(())
+}
+
+pub async fn list_lockouts(client: &Client<'_>) -> Result<()> {
+    let lockouts = match client.list_lockouts().await {
+        Ok(lockouts) => lockouts,
+        Err(err) => {
+            eprintln!("Could not list lockouts: {:?}", err);
+            return Err(err);
+        }
+    };
+    if lockouts.is_empty() {
+        println!("No failed authentication attempts (empty set)");
+        return Ok(());
+    }
+    let table = lockouts
+        .into_iter()
+        .map(|lockout| {
+            vec![
+                lockout
+                    .ip
+                    .map(|ip| format!("client {ip}"))
+                    .or_else(|| lockout.username.map(|username| format!("user {username}")))
+                    .unwrap_or_default()
+                    .cell(),
+                lockout.failures.cell(),
+                if lockout.locked { "locked" } else { "delayed" }.cell(),
+                format!("{}s", lockout.retry_after).cell(),
+            ]
+        })
+        .collect::<Vec<Vec<CellStruct>>>()
+        .table()
+        .title(vec![
+            "Subject".cell(),
+            "Failures".cell(),
+            "State".cell(),
+            "Retry after".cell(),
+        ])
+        .bold(true);
+    println!("{}", table.display().unwrap());
+    Ok(())
+}
+
+pub async fn clear_lockouts(
+    client: &Client<'_>,
+    ip: Option<&str>,
+    username: Option<&str>,
+) -> Result<()> {
+    if let Err(err) = client.clear_lockouts(ip, username).await {
+        eprintln!("Could not clear lockouts: {:?}", err);
+        return Err(err);
+    }
+    println!("Successfully cleared lockouts");
     Ok(())
 }
@@ -1,12 +1,17 @@
 use std::{
     env,
     fmt::Display,
-    fs::File,
+    fs::{File, OpenOptions},
     io::{self, Write},
     path::Path,
 };
+#[cfg(unix)]
+use std::{
+    fs::Permissions,
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use tokio::fs;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -14,6 +19,7 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub enum Error {
     IO(io::Error),
     Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
     HomeDir,
 }
 
@@ -25,8 +31,9 @@ impl Display for Error {
             match self {
                 Error::IO(err) => format!("{err}"),
                 Error::Parse(err) => format!("{err}"),
+                Error::Serialize(err) => format!("{err}"),
                 Error::HomeDir =>
-                    format!("Could not determine your home directory: does it exist?"),
+                    "Could not determine your home directory: does it exist?".to_string(),
             }
         )
     }
@@ -44,11 +51,21 @@ impl From<toml::de::Error> for Error {
     }
 }
 
-#[derive(Deserialize)]
+impl From<toml::ser::Error> for Error {
+    fn from(err: toml::ser::Error) -> Self {
+        Self::Serialize(err)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
 pub struct Config {
     pub url: String,
     pub user: String,
-    pub password: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// An API token which is used instead of the password, written by `yaus login`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
 }
 
 impl Default for Config {
@@ -56,7 +73,8 @@ impl Default for Config {
         Self {
             url: "https://example.com".to_string(),
             user: "admin".to_string(),
-            password: "admin".to_string(),
+            password: Some("admin".to_string()),
+            token: None,
         }
     }
 }
@@ -86,10 +104,31 @@ pub async fn read_config(file_path: &str) -> Result<Config> {
         false => {
             // Create the file and it's parent directories
             fs::create_dir_all(&path.parent().unwrap()).await?;
-            let mut file = File::create(path)?;
+            let mut file = create_private(path)?;
             file.write_all(include_bytes!("default_config.toml"))?;
             println!("Created new config file (at `{file_path}`)");
             Ok(Config::default())
         }
     }
 }
+
+/// Replaces the configuration file with the specified configuration
+pub async fn write_config(file_path: &str, config: &Config) -> Result<()> {
+    let mut file = create_private(Path::new(file_path))?;
+    file.write_all(toml::to_string(config)?.as_bytes())?;
+    Ok(())
+}
+
+/// Creates or truncates the configuration file, which only its owner may access as it holds the
+/// password and API token
+fn create_private(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let file = options.open(path)?;
+    // The mode only applies to newly created files, existing ones are tightened as well
+    #[cfg(unix)]
+    file.set_permissions(Permissions::from_mode(0o600))?;
+    Ok(file)
+}
@@ -1,11 +1,21 @@
-use api::{Client, Error, User};
-use clap::Parser;
+use api::{Client, Credentials, Error};
+use clap::{Parser, Subcommand};
 use std::process;
 
 mod api;
 mod cli;
 mod config;
 
+/// The redirect types known to the server
+const REDIRECT_TYPES: [&str; 6] = [
+    "moved_permanently",
+    "found",
+    "temporary_redirect",
+    "permanent_redirect",
+    "meta_refresh",
+    "frame",
+];
+
 #[derive(Parser, PartialEq)]
 #[clap(author, version, about)]
 enum Yaus {
@@ -13,11 +23,71 @@ enum Yaus {
     /// Create a new short-URL
     Add {
         /// The short id of the new redirect
+        /// If this is the only argument, it is used as the target URL and the short id is generated
+        #[clap(required = true, value_name = "SHORT")]
+        short_or_target_url: String,
+        /// The target URL of the new redirect, `{1}`, `{2}`, … and `{query}` are filled with the
+        /// path following the short id
+        target_url: Option<String>,
+        /// When the redirect should stop working (RFC 3339, e.g. `2022-12-31T23:59:59Z`)
+        #[clap(long)]
+        expires_at: Option<String>,
+        /// How often the redirect may be visited before it stops working
+        #[clap(long)]
+        max_visits: Option<i32>,
+        /// Where visitors should be sent once the redirect has expired or is used up
+        #[clap(long)]
+        fallback_url: Option<String>,
+        /// A human readable name of the redirect
+        #[clap(long)]
+        title: Option<String>,
+        /// Why the redirect exists
+        #[clap(long)]
+        description: Option<String>,
+        /// Attach a tag to the redirect, can be specified multiple times
+        #[clap(short, long = "tag")]
+        tags: Vec<String>,
+        /// How visitors are sent to the target, the server-wide default is used if omitted
+        #[clap(long, possible_values = REDIRECT_TYPES)]
+        redirect_type: Option<String>,
+        /// Append the path following the short id to the target URL
+        #[clap(long)]
+        forward_path: bool,
+        /// Merge the query string of visits into the one of the target URL
+        #[clap(long)]
+        forward_query: bool,
+        /// The domain of the redirect, omit it for the default domain
+        #[clap(short, long)]
+        domain: Option<String>,
+    },
+    #[clap(arg_required_else_help = true)]
+    /// Change the target URL of an existing redirect
+    Edit {
+        /// The short id of the redirect
         #[clap(required = true)]
         short: String,
-        /// The target URL of the new redirect
+        /// The new target URL of the redirect
         #[clap(required = true)]
         target_url: String,
+        /// Also change how visitors are sent to the target
+        #[clap(long, possible_values = REDIRECT_TYPES)]
+        redirect_type: Option<String>,
+        /// The domain of the redirect, omit it for the default domain
+        #[clap(short, long)]
+        domain: Option<String>,
+    },
+    #[clap(arg_required_else_help = true)]
+    /// Rename an existing redirect
+    Mv {
+        /// The current short id of the redirect
+        #[clap(required = true)]
+        short: String,
+        /// The new short id of the redirect
+        #[clap(required = true)]
+        new_short: String,
+        /// The domain of the redirect, omit it for the default domain
+        #[clap(short, long)]
+        domain: Option<String>,
     },
     #[clap(arg_required_else_help = true)]
     /// Delete an existing redirect
@@ -25,23 +95,183 @@ enum Yaus {
         /// The short id of the redirect
         #[clap(required = true)]
         short: Vec<String>,
+        /// The domain of the redirect, omit it for the default domain
+        #[clap(short, long)]
+        domain: Option<String>,
     },
     /// Follow a redirect and print its target URL
     Get {
         /// The short id of the redirect
         #[clap(required = true)]
         short: String,
+        /// Print all details of the redirect
+        #[clap(short, long)]
+        verbose: bool,
+        /// The domain of the redirect, omit it for the default domain
+        #[clap(short, long)]
+        domain: Option<String>,
     },
     /// Print a list of all configured redirects
     List {
         /// How many items should be displayed at maximum
         #[clap(short, long)]
         max: Option<u32>,
+        /// Only list redirects whose short id starts with this prefix
+        #[clap(short, long)]
+        prefix: Option<String>,
+        /// Only list redirects whose target URL contains this text
+        #[clap(short, long)]
+        search: Option<String>,
+        /// Order the redirects by their short id or by their creation time
+        #[clap(long, default_value = "short", possible_values = ["short", "created"])]
+        sort: String,
+        /// Only list redirects carrying this tag
+        #[clap(short, long)]
+        tag: Option<String>,
+        /// The domain of the redirect, omit it for the default domain
+        #[clap(short, long)]
+        domain: Option<String>,
+    },
+    /// Manage the tags of redirects
+    Tag {
+        #[clap(subcommand)]
+        action: TagAction,
     },
+    /// Manage the users of the server
+    User {
+        #[clap(subcommand)]
+        action: UserAction,
+    },
+    /// Print how often a redirect was visited
+    Stats {
+        /// The short id of the redirect
+        #[clap(required = true)]
+        short: String,
+        /// How many of the most recent visits should be displayed
+        #[clap(short, long, default_value_t = 10)]
+        last: u32,
+        /// The domain of the redirect, omit it for the default domain
+        #[clap(short, long)]
+        domain: Option<String>,
+    },
+    /// Inspect and clear failed authentication attempts (requires admin privileges)
+    Lockout {
+        #[clap(subcommand)]
+        action: LockoutAction,
+    },
+    /// Manage personal API tokens
+    Token {
+        #[clap(subcommand)]
+        action: TokenAction,
+    },
+    /// Exchange the password for an API token which is stored in the configuration instead
+    Login,
     /// Prints the location of the configuration file
     Config,
 }
 
+#[derive(Subcommand, PartialEq)]
+enum TagAction {
+    #[clap(arg_required_else_help = true)]
+    /// Attach tags to a redirect
+    Add {
+        /// The short id of the redirect
+        #[clap(required = true)]
+        short: String,
+        /// The tags to attach
+        #[clap(required = true)]
+        tags: Vec<String>,
+        /// The domain of the redirect, omit it for the default domain
+        #[clap(short, long)]
+        domain: Option<String>,
+    },
+    #[clap(arg_required_else_help = true)]
+    /// Remove tags from a redirect
+    Rm {
+        /// The short id of the redirect
+        #[clap(required = true)]
+        short: String,
+        /// The tags to remove
+        #[clap(required = true)]
+        tags: Vec<String>,
+        /// The domain of the redirect, omit it for the default domain
+        #[clap(short, long)]
+        domain: Option<String>,
+    },
+    /// Print all tags which are in use
+    Ls,
+}
+
+#[derive(Subcommand, PartialEq)]
+enum UserAction {
+    /// Print all users (requires admin privileges)
+    Ls,
+    #[clap(arg_required_else_help = true)]
+    /// Create a new user, its password is prompted for (requires admin privileges)
+    Add {
+        /// The name of the new user
+        #[clap(required = true)]
+        username: String,
+        /// Viewers may only list redirects, editors may also manage their own redirects
+        /// and admins may manage everything
+        #[clap(short, long, default_value = "editor", possible_values = ["viewer", "editor", "admin"])]
+        role: String,
+    },
+    /// Change the password of a user, its password is prompted for
+    Passwd {
+        /// The user whose password should be changed, defaults to the configured user
+        username: Option<String>,
+    },
+    #[clap(arg_required_else_help = true)]
+    /// Delete a user, its redirects are kept (requires admin privileges)
+    Rm {
+        /// The name of the user
+        #[clap(required = true)]
+        username: String,
+    },
+}
+
+#[derive(Subcommand, PartialEq)]
+enum LockoutAction {
+    /// Print all clients and users with failed authentication attempts
+    Ls,
+    /// Clear the failed attempts of a client or user, or of everyone if neither is specified
+    Clear {
+        /// The IP address of the client
+        #[clap(long)]
+        ip: Option<String>,
+        /// The name of the user
+        #[clap(long)]
+        username: Option<String>,
+    },
+}
+
+#[derive(Subcommand, PartialEq)]
+enum TokenAction {
+    /// Print all API tokens of the configured user
+    Ls,
+    #[clap(arg_required_else_help = true)]
+    /// Create a new API token and print it
+    Add {
+        /// Helps to recognize the token later
+        #[clap(required = true)]
+        name: String,
+        /// What the token may be used for: `read`, `write` or `admin`, can be specified multiple times
+        #[clap(short, long = "scope")]
+        scopes: Vec<String>,
+        /// When the token should stop working (RFC 3339, e.g. `2022-12-31T23:59:59Z`)
+        #[clap(long)]
+        expires_at: Option<String>,
+    },
+    #[clap(arg_required_else_help = true)]
+    /// Revoke an API token
+    Rm {
+        /// The id of the token
+        #[clap(required = true)]
+        id: i32,
+    },
+}
+
 #[tokio::main]
 async fn main() {
     // Get the configuration file location
@@ -69,17 +299,33 @@ async fn main() {
             process::exit(1);
         }
     };
-    println!("Connecting to server ({}@{})...", conf.user, conf.url);
-    // Create the Yaus client, handle potential errors
-    let client = match Client::new(
-        &conf.url,
-        User {
+    // Logging in always uses the password, it is prompted for if it is not configured
+    let password = match (&args, &conf.password) {
+        (Yaus::Login, None) => {
+            match rpassword::read_password_from_tty(Some(&format!("Password of {}: ", conf.user))) {
+                Ok(password) => Some(password),
+                Err(err) => {
+                    eprintln!("Could not read password: {err}");
+                    process::exit(1);
+                }
+            }
+        }
+        _ => conf.password.clone(),
+    };
+    let credentials = match (&conf.token, &password) {
+        (Some(token), _) if args != Yaus::Login => Credentials::Token(token),
+        (_, Some(password)) => Credentials::Password {
             username: &conf.user,
-            password: &conf.password,
+            password,
         },
-    )
-    .await
-    {
+        _ => {
+            eprintln!("Neither a password nor an API token is configured, use `yaus login`");
+            process::exit(1);
+        }
+    };
+    println!("Connecting to server ({}@{})...", conf.user, conf.url);
+    // Create the Yaus client, handle potential errors
+    let client = match Client::new(&conf.url, credentials).await {
         Ok(client) => client,
         Err(err) => {
             eprintln!(
@@ -96,21 +342,224 @@ async fn main() {
     // Execute different functions based on the Clap subcommand
     let success = match args {
         Yaus::Config => unreachable!("This case should have been handled beforehand"),
-        Yaus::List { max } => {
-            cli::list_redirects(&client, if let Some(max) = max { max } else { u32::MAX })
-                .await
-                .is_ok()
+        Yaus::Login => match cli::login(&client).await {
+            // The token replaces the password in the configuration file
+            Ok(token) => match config::write_config(
+                &config_file_path,
+                &config::Config {
+                    url: conf.url.clone(),
+                    user: conf.user.clone(),
+                    password: None,
+                    token: Some(token),
+                },
+            )
+            .await
+            {
+                Ok(_) => {
+                    println!("Successfully logged in, the password was replaced by an API token in `{config_file_path}`");
+                    true
+                }
+                Err(err) => {
+                    eprintln!(
+                        "Could not write configuration file (at `{config_file_path}`): {err}"
+                    );
+                    false
+                }
+            },
+            Err(_) => false,
+        },
+        Yaus::Lockout {
+            action: LockoutAction::Ls,
+        } => cli::list_lockouts(&client).await.is_ok(),
+        Yaus::Lockout {
+            action: LockoutAction::Clear { ip, username },
+        } => cli::clear_lockouts(&client, ip.as_deref(), username.as_deref())
+            .await
+            .is_ok(),
+        Yaus::Token {
+            action: TokenAction::Ls,
+        } => cli::list_tokens(&client).await.is_ok(),
+        Yaus::Token {
+            action:
+                TokenAction::Add {
+                    name,
+                    scopes,
+                    expires_at,
+                },
+        } => cli::create_token(
+            &client,
+            &api::NewToken {
+                name: &name,
+                scopes: (!scopes.is_empty()).then_some(scopes.as_slice()),
+                expires_at: expires_at.as_deref(),
+            },
+        )
+        .await
+        .is_ok(),
+        Yaus::Token {
+            action: TokenAction::Rm { id },
+        } => cli::delete_token(&client, id).await.is_ok(),
+        Yaus::List {
+            max,
+            prefix,
+            search,
+            sort,
+            tag,
+            domain,
+        } => cli::list_redirects(
+            &client,
+            &api::ListQuery {
+                domain: domain.as_deref(),
+                sort: &sort,
+                prefix: prefix.as_deref(),
+                search: search.as_deref(),
+                tag: tag.as_deref(),
+            },
+            max.unwrap_or(u32::MAX),
+        )
+        .await
+        .is_ok(),
+        Yaus::Get {
+            short,
+            verbose,
+            domain,
+        } => cli::get_target(&client, &short, domain.as_deref(), verbose)
+            .await
+            .is_ok(),
+        Yaus::Stats {
+            short,
+            last,
+            domain,
+        } => cli::show_stats(&client, &short, domain.as_deref(), last)
+            .await
+            .is_ok(),
+        Yaus::Add {
+            short_or_target_url,
+            target_url,
+            expires_at,
+            max_visits,
+            fallback_url,
+            title,
+            description,
+            tags,
+            domain,
+            redirect_type,
+            forward_path,
+            forward_query,
+        } => {
+            // If only one argument is given, the server generates the short id
+            let (short, target_url) = match target_url {
+                Some(target_url) => (Some(short_or_target_url), target_url),
+                None => (None, short_or_target_url),
+            };
+            cli::create_redirect(
+                &client,
+                &api::NewRedirect {
+                    domain,
+                    short,
+                    target_url,
+                    expires_at,
+                    fallback_url,
+                    max_visits,
+                    title,
+                    description,
+                    tags,
+                    redirect_type,
+                    forward_path,
+                    forward_query,
+                },
+            )
+            .await
+            .is_ok()
         }
-        Yaus::Get { short } => cli::get_target(&client, &short).await.is_ok(),
-        Yaus::Add { short, target_url } => {
-            cli::create_redirect(&client, &api::Redirect { short, target_url })
+        Yaus::Edit {
+            short,
+            target_url,
+            domain,
+            redirect_type,
+        } => cli::update_redirect(
+            &client,
+            &short,
+            domain.as_deref(),
+            &api::RedirectUpdate {
+                target_url: Some(target_url),
+                redirect_type,
+                ..Default::default()
+            },
+        )
+        .await
+        .is_ok(),
+        Yaus::Mv {
+            short,
+            new_short,
+            domain,
+        } => cli::update_redirect(
+            &client,
+            &short,
+            domain.as_deref(),
+            &api::RedirectUpdate {
+                short: Some(new_short),
+                ..Default::default()
+            },
+        )
+        .await
+        .is_ok(),
+        Yaus::Tag {
+            action:
+                TagAction::Add {
+                    short,
+                    tags,
+                    domain,
+                },
+        } => cli::add_tags(&client, &short, domain.as_deref(), &tags)
+            .await
+            .is_ok(),
+        Yaus::Tag {
+            action:
+                TagAction::Rm {
+                    short,
+                    tags,
+                    domain,
+                },
+        } => cli::remove_tags(&client, &short, domain.as_deref(), &tags)
+            .await
+            .is_ok(),
+        Yaus::Tag {
+            action: TagAction::Ls,
+        } => cli::list_tags(&client).await.is_ok(),
+        Yaus::User {
+            action: UserAction::Ls,
+        } => cli::list_users(&client).await.is_ok(),
+        Yaus::User {
+            action: UserAction::Add { username, role },
+        } => match cli::prompt_new_password() {
+            Some(password) => cli::create_user(&client, &username, &password, &role)
                 .await
-                .is_ok()
-        }
-        Yaus::Del { short } => {
+                .is_ok(),
+            None => false,
+        },
+        Yaus::User {
+            action: UserAction::Passwd { username },
+        } => match cli::prompt_new_password() {
+            Some(password) => cli::change_password(
+                &client,
+                username.as_deref().unwrap_or(&conf.user),
+                &password,
+            )
+            .await
+            .is_ok(),
+            None => false,
+        },
+        Yaus::User {
+            action: UserAction::Rm { username },
+        } => cli::delete_user(&client, &username).await.is_ok(),
+        Yaus::Del { short, domain } => {
             let mut success = true;
             for item in short {
-                if let Err(_) = cli::delete_redirect(&client, &item).await {
+                if cli::delete_redirect(&client, &item, domain.as_deref())
+                    .await
+                    .is_err()
+                {
                     success = false;
                     break;
                 }
@@ -0,0 +1,17 @@
+-- Creates the visit table which records every successful redirect
+CREATE TABLE
+IF NOT EXISTS
+visit(
+    id          BIGSERIAL       NOT NULL,
+    short       VARCHAR(20)     NOT NULL,
+    visited_at  TIMESTAMPTZ     NOT NULL DEFAULT now(),
+    referrer    TEXT,
+    user_agent  TEXT,
+    ip          VARCHAR(45),
+    PRIMARY KEY(id),
+    FOREIGN KEY(short) REFERENCES url(short) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX
+IF NOT EXISTS
+visit_short_visited_at ON visit(short, visited_at);
@@ -0,0 +1,26 @@
+-- Creates the table containing aggregated visit counters
+CREATE TABLE
+IF NOT EXISTS
+visit_rollup(
+    short           VARCHAR(20)     NOT NULL,
+    bucket          VARCHAR(4)      NOT NULL,
+    bucket_start    TIMESTAMPTZ     NOT NULL,
+    visits          BIGINT          NOT NULL,
+    PRIMARY KEY(short, bucket, bucket_start),
+    FOREIGN KEY(short) REFERENCES url(short) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+-- Remembers up to which point in time visits have been compacted into rollups
+CREATE TABLE
+IF NOT EXISTS
+visit_rollup_state(
+    id                  BOOLEAN         NOT NULL DEFAULT TRUE,
+    rolled_up_until     TIMESTAMPTZ     NOT NULL,
+    PRIMARY KEY(id),
+    CHECK(id)
+);
+
+INSERT INTO
+visit_rollup_state(rolled_up_until)
+VALUES('1970-01-01 00:00:00+00')
+ON CONFLICT (id) DO NOTHING;
@@ -0,0 +1,22 @@
+-- Adds an optional expiry date and fallback URL to short URLs
+ALTER TABLE url
+ADD COLUMN IF NOT EXISTS expires_at     TIMESTAMPTZ,
+ADD COLUMN IF NOT EXISTS fallback_url   VARCHAR(500);
+
+CREATE INDEX
+IF NOT EXISTS
+url_expires_at ON url(expires_at)
+WHERE expires_at IS NOT NULL;
+
+-- Creates the table which keeps expired short URLs after they have been swept
+CREATE TABLE
+IF NOT EXISTS
+url_archive(
+    id              BIGSERIAL       NOT NULL,
+    short           VARCHAR(20)     NOT NULL,
+    target_url      VARCHAR(500)    NOT NULL,
+    expires_at      TIMESTAMPTZ     NOT NULL,
+    fallback_url    VARCHAR(500),
+    archived_at     TIMESTAMPTZ     NOT NULL DEFAULT now(),
+    PRIMARY KEY(id)
+);
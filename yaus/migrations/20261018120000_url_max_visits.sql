@@ -0,0 +1,3 @@
+-- Adds an optional limit of remaining visits to short URLs
+ALTER TABLE url
+ADD COLUMN IF NOT EXISTS max_visits INTEGER CHECK(max_visits >= 0);
@@ -0,0 +1,4 @@
+-- Creates the counter used for sequentially generated short ids
+CREATE SEQUENCE
+IF NOT EXISTS
+short_id_seq;
@@ -0,0 +1,13 @@
+-- Remembers when each short URL was created, existing short URLs count as created now
+ALTER TABLE url
+ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+-- Supports keyset pagination ordered by creation time
+CREATE INDEX
+IF NOT EXISTS
+url_created_at_short ON url(created_at, short);
+
+-- Supports filtering short ids by prefix
+CREATE INDEX
+IF NOT EXISTS
+url_short_pattern ON url(short varchar_pattern_ops);
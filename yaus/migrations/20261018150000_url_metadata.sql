@@ -0,0 +1,9 @@
+-- Adds descriptive metadata to short URLs
+ALTER TABLE url
+ADD COLUMN IF NOT EXISTS updated_at     TIMESTAMPTZ     NOT NULL DEFAULT now(),
+ADD COLUMN IF NOT EXISTS created_by     VARCHAR(100),
+ADD COLUMN IF NOT EXISTS title          VARCHAR(200),
+ADD COLUMN IF NOT EXISTS description    TEXT;
+
+-- Existing short URLs have not been modified since their creation
+UPDATE url SET updated_at=created_at;
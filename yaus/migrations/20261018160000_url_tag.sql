@@ -0,0 +1,13 @@
+-- Creates the table which attaches tags to short URLs
+CREATE TABLE
+IF NOT EXISTS
+url_tag(
+    short       VARCHAR(20)     NOT NULL,
+    tag         VARCHAR(50)     NOT NULL,
+    PRIMARY KEY(short, tag),
+    FOREIGN KEY(short) REFERENCES url(short) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX
+IF NOT EXISTS
+url_tag_tag ON url_tag(tag);
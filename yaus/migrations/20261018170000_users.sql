@@ -0,0 +1,10 @@
+-- Creates the table which stores the accounts allowed to use the API
+CREATE TABLE
+IF NOT EXISTS
+users(
+    username        VARCHAR(100)    PRIMARY KEY,
+    -- An argon2 hash in the PHC string format
+    password_hash   TEXT            NOT NULL,
+    is_admin        BOOLEAN         NOT NULL DEFAULT false,
+    created_at      TIMESTAMPTZ     NOT NULL DEFAULT now()
+);
@@ -0,0 +1,19 @@
+-- Creates the table which stores personal API tokens
+CREATE TABLE
+IF NOT EXISTS
+api_token(
+    id              SERIAL          PRIMARY KEY,
+    username        VARCHAR(100)    NOT NULL,
+    name            VARCHAR(100)    NOT NULL,
+    -- The hex encoded SHA-256 hash of the token, the token itself is never stored
+    token_hash      CHAR(64)        NOT NULL UNIQUE,
+    scopes          VARCHAR(10)[]   NOT NULL,
+    expires_at      TIMESTAMPTZ,
+    created_at      TIMESTAMPTZ     NOT NULL DEFAULT now(),
+    last_used_at    TIMESTAMPTZ,
+    FOREIGN KEY(username) REFERENCES users(username) ON DELETE CASCADE ON UPDATE CASCADE
+);
+
+CREATE INDEX
+IF NOT EXISTS
+api_token_username ON api_token(username);
@@ -0,0 +1,10 @@
+-- Replaces the admin flag of users with roles
+CREATE TYPE user_role AS ENUM ('viewer', 'editor', 'admin');
+
+ALTER TABLE users
+ADD COLUMN IF NOT EXISTS role   user_role   NOT NULL DEFAULT 'editor';
+
+UPDATE users SET role='admin' WHERE is_admin;
+
+ALTER TABLE users
+DROP COLUMN IF EXISTS is_admin;
@@ -0,0 +1,45 @@
+-- Scopes short ids by the domain they are served on
+-- The empty domain contains all short URLs which are served on hosts without a configured domain
+ALTER TABLE visit DROP CONSTRAINT IF EXISTS visit_short_fkey;
+ALTER TABLE visit_rollup DROP CONSTRAINT IF EXISTS visit_rollup_short_fkey;
+ALTER TABLE url_tag DROP CONSTRAINT IF EXISTS url_tag_short_fkey;
+
+ALTER TABLE url
+ADD COLUMN IF NOT EXISTS domain VARCHAR(253) NOT NULL DEFAULT '',
+DROP CONSTRAINT url_pkey,
+ADD PRIMARY KEY(domain, short);
+
+ALTER TABLE visit
+ADD COLUMN IF NOT EXISTS domain VARCHAR(253) NOT NULL DEFAULT '',
+ADD FOREIGN KEY(domain, short) REFERENCES url(domain, short) ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE visit_rollup
+ADD COLUMN IF NOT EXISTS domain VARCHAR(253) NOT NULL DEFAULT '',
+DROP CONSTRAINT visit_rollup_pkey,
+ADD PRIMARY KEY(domain, short, bucket, bucket_start),
+ADD FOREIGN KEY(domain, short) REFERENCES url(domain, short) ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE url_tag
+ADD COLUMN IF NOT EXISTS domain VARCHAR(253) NOT NULL DEFAULT '',
+DROP CONSTRAINT url_tag_pkey,
+ADD PRIMARY KEY(domain, short, tag),
+ADD FOREIGN KEY(domain, short) REFERENCES url(domain, short) ON DELETE CASCADE ON UPDATE CASCADE;
+
+ALTER TABLE url_archive
+ADD COLUMN IF NOT EXISTS domain VARCHAR(253) NOT NULL DEFAULT '';
+
+-- Listings are always restricted to a single domain
+DROP INDEX IF EXISTS visit_short_visited_at;
+CREATE INDEX
+IF NOT EXISTS
+visit_domain_short_visited_at ON visit(domain, short, visited_at);
+
+DROP INDEX IF EXISTS url_created_at_short;
+CREATE INDEX
+IF NOT EXISTS
+url_domain_created_at_short ON url(domain, created_at, short);
+
+DROP INDEX IF EXISTS url_short_pattern;
+CREATE INDEX
+IF NOT EXISTS
+url_domain_short_pattern ON url(domain, short varchar_pattern_ops);
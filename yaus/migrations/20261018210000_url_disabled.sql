@@ -0,0 +1,3 @@
+-- Short URLs whose target domain has become blocked are disabled until it is allowed again
+ALTER TABLE url
+ADD COLUMN IF NOT EXISTS disabled_reason VARCHAR(300);
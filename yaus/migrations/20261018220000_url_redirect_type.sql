@@ -0,0 +1,12 @@
+-- Remembers how a short URL sends its visitors to the target, NULL uses the server-wide default
+CREATE TYPE redirect_type AS ENUM (
+    'moved_permanently',
+    'found',
+    'temporary_redirect',
+    'permanent_redirect',
+    'meta_refresh',
+    'frame'
+);
+
+ALTER TABLE url
+ADD COLUMN IF NOT EXISTS redirect_type redirect_type;
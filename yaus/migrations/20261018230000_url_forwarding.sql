@@ -0,0 +1,4 @@
+-- Whether the remaining path and the query string of a visit are passed on to the target
+ALTER TABLE url
+ADD COLUMN IF NOT EXISTS forward_path   BOOLEAN NOT NULL DEFAULT FALSE,
+ADD COLUMN IF NOT EXISTS forward_query  BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,6 @@
+-- Finds short ids resembling unknown ones, for suggestions
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+CREATE INDEX
+IF NOT EXISTS
+url_short_trigram ON url USING GIN (short gin_trgm_ops);
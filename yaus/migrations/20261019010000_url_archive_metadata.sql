@@ -0,0 +1,14 @@
+-- Keeps everything describing a short URL in the archive, including the tags removed along with it
+-- Short URLs archived before did not record the creation and modification times
+ALTER TABLE url_archive
+ADD COLUMN IF NOT EXISTS max_visits         INTEGER,
+ADD COLUMN IF NOT EXISTS created_at         TIMESTAMPTZ,
+ADD COLUMN IF NOT EXISTS updated_at         TIMESTAMPTZ,
+ADD COLUMN IF NOT EXISTS created_by         VARCHAR(100),
+ADD COLUMN IF NOT EXISTS title              VARCHAR(200),
+ADD COLUMN IF NOT EXISTS description        TEXT,
+ADD COLUMN IF NOT EXISTS tags               TEXT[]          NOT NULL DEFAULT '{}',
+ADD COLUMN IF NOT EXISTS disabled_reason    VARCHAR(300),
+ADD COLUMN IF NOT EXISTS redirect_type      redirect_type,
+ADD COLUMN IF NOT EXISTS forward_path       BOOLEAN         NOT NULL DEFAULT FALSE,
+ADD COLUMN IF NOT EXISTS forward_query      BOOLEAN         NOT NULL DEFAULT FALSE;
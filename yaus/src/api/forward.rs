@@ -0,0 +1,138 @@
+use url::{form_urlencoded, ParseError, Url};
+
+use crate::config::QueryConflict;
+
+/// Appends the path following the short id to the target and merges the query string of the visit
+/// into the one of the target
+pub fn forward(
+    target: &str,
+    rest: Option<&str>,
+    query: Option<&str>,
+    conflict: QueryConflict,
+) -> Result<String, ParseError> {
+    let rest = rest.filter(|rest| !rest.is_empty());
+    let query = query.filter(|query| !query.is_empty());
+    // Targets are passed on verbatim if there is nothing to forward
+    if rest.is_none() && query.is_none() {
+        return Ok(target.to_owned());
+    }
+    let mut target = Url::parse(target)?;
+    if let Some(rest) = rest {
+        // Dot segments would escape the path of the target
+        if let Ok(mut segments) = target.path_segments_mut() {
+            segments.pop_if_empty().extend(
+                rest.split('/')
+                    .filter(|segment| *segment != "." && *segment != ".."),
+            );
+        }
+    }
+    if let Some(query) = query {
+        let existing = target.query_pairs().into_owned().collect::<Vec<_>>();
+        let incoming = form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect::<Vec<_>>();
+        let contains =
+            |pairs: &[(String, String)], name: &str| pairs.iter().any(|(other, _)| other == name);
+        let merged = match conflict {
+            QueryConflict::Request => existing
+                .iter()
+                .filter(|(name, _)| !contains(&incoming, name))
+                .chain(&incoming)
+                .collect::<Vec<_>>(),
+            QueryConflict::Target => existing
+                .iter()
+                .chain(
+                    incoming
+                        .iter()
+                        .filter(|(name, _)| !contains(&existing, name)),
+                )
+                .collect(),
+            QueryConflict::Append => existing.iter().chain(&incoming).collect(),
+        };
+        target.set_query(None);
+        if !merged.is_empty() {
+            target.query_pairs_mut().extend_pairs(merged);
+        }
+    }
+    Ok(target.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_target_without_anything_to_forward() {
+        assert_eq!(
+            forward(
+                "https://example.com",
+                Some(""),
+                Some(""),
+                QueryConflict::Request
+            )
+            .unwrap(),
+            "https://example.com"
+        );
+    }
+
+    #[test]
+    fn appends_path() {
+        assert_eq!(
+            forward(
+                "https://jira.example.com/browse/",
+                Some("ABC-123"),
+                None,
+                QueryConflict::Request
+            )
+            .unwrap(),
+            "https://jira.example.com/browse/ABC-123"
+        );
+        assert_eq!(
+            forward(
+                "https://example.com/docs",
+                Some("a b/c?d"),
+                None,
+                QueryConflict::Request
+            )
+            .unwrap(),
+            "https://example.com/docs/a%20b/c%3Fd"
+        );
+    }
+
+    #[test]
+    fn drops_dot_segments() {
+        assert_eq!(
+            forward(
+                "https://example.com/docs/",
+                Some("../../admin"),
+                None,
+                QueryConflict::Request
+            )
+            .unwrap(),
+            "https://example.com/docs/admin"
+        );
+    }
+
+    #[test]
+    fn merges_query_by_policy() {
+        let target = "https://example.com/?a=1&b=2";
+        let query = Some("b=3&c=4");
+        assert_eq!(
+            forward(target, None, query, QueryConflict::Request).unwrap(),
+            "https://example.com/?a=1&b=3&c=4"
+        );
+        assert_eq!(
+            forward(target, None, query, QueryConflict::Target).unwrap(),
+            "https://example.com/?a=1&b=2&c=4"
+        );
+        assert_eq!(
+            forward(target, None, query, QueryConflict::Append).unwrap(),
+            "https://example.com/?a=1&b=2&b=3&c=4"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_target() {
+        assert!(forward("not a url", Some("a"), None, QueryConflict::Request).is_err());
+    }
+}
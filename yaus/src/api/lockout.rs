@@ -0,0 +1,185 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::web::{Data, Query};
+use actix_web::HttpResponse;
+use serde::{Deserialize, Serialize};
+
+use crate::api::GenericResponse;
+use crate::config::LockoutConfig;
+use crate::State;
+
+/// Failed authentication attempts are tracked per client IP address and per username
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Subject {
+    Ip(IpAddr),
+    Username(String),
+}
+
+struct Failures {
+    count: u32,
+    /// Until then, further attempts are rejected without checking their credentials
+    blocked_until: Instant,
+}
+
+/// Applies an exponential back-off to failed authentication attempts
+/// and locks clients and usernames out once they exceed the configured threshold
+pub struct LockoutTracker {
+    config: LockoutConfig,
+    failures: Mutex<HashMap<Subject, Failures>>,
+}
+
+#[derive(Serialize)]
+pub struct Lockout {
+    ip: Option<IpAddr>,
+    username: Option<String>,
+    /// The amount of consecutive failed attempts
+    failures: u32,
+    /// Whether the threshold was reached, otherwise further attempts are only delayed
+    locked: bool,
+    /// How many seconds remain until further attempts are accepted
+    retry_after: u64,
+}
+
+impl LockoutTracker {
+    pub fn new(config: LockoutConfig) -> Self {
+        Self {
+            config,
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn subjects(ip: Option<IpAddr>, username: Option<&str>) -> Vec<Subject> {
+        ip.map(Subject::Ip)
+            .into_iter()
+            .chain(username.map(|username| Subject::Username(username.to_string())))
+            .collect()
+    }
+
+    /// Returns how long an authentication attempt has to wait, if it is blocked
+    pub fn check(&self, ip: Option<IpAddr>, username: Option<&str>) -> Option<Duration> {
+        if self.config.threshold == 0 {
+            return None;
+        }
+        let now = Instant::now();
+        let failures = self.failures.lock().expect("Lockout lock is poisoned");
+        Self::subjects(ip, username)
+            .iter()
+            .filter_map(|subject| failures.get(subject))
+            .filter(|failures| failures.blocked_until > now)
+            .map(|failures| failures.blocked_until - now)
+            .max()
+    }
+
+    pub fn record_failure(&self, ip: Option<IpAddr>, username: Option<&str>) {
+        if self.config.threshold == 0 {
+            return;
+        }
+        let now = Instant::now();
+        let lockout_duration = Duration::from_secs(self.config.duration);
+        let mut failures = self.failures.lock().expect("Lockout lock is poisoned");
+
+        // Failures are forgotten once the subject has been quiet for as long as a lockout lasts
+        failures.retain(|_, failures| failures.blocked_until + lockout_duration > now);
+
+        for subject in Self::subjects(ip, username) {
+            let entry = failures.entry(subject.clone()).or_insert(Failures {
+                count: 0,
+                blocked_until: now,
+            });
+            entry.count += 1;
+            if entry.count >= self.config.threshold {
+                entry.blocked_until = now + lockout_duration;
+                if entry.count == self.config.threshold {
+                    warn!(
+                        "Locking out {} for {}s after {} failed authentication attempts",
+                        match &subject {
+                            Subject::Ip(ip) => format!("client `{ip}`"),
+                            Subject::Username(username) => format!("user `{username}`"),
+                        },
+                        self.config.duration,
+                        entry.count
+                    );
+                }
+            } else {
+                let delay = self
+                    .config
+                    .base_delay
+                    .saturating_mul(1 << (entry.count - 1).min(32))
+                    .min(self.config.duration);
+                entry.blocked_until = now + Duration::from_secs(delay);
+            }
+        }
+    }
+
+    /// Forgets the failed attempts of a client and a user after they authenticated successfully
+    pub fn record_success(&self, ip: Option<IpAddr>, username: &str) {
+        let mut failures = self.failures.lock().expect("Lockout lock is poisoned");
+        for subject in Self::subjects(ip, Some(username)) {
+            failures.remove(&subject);
+        }
+    }
+
+    pub fn list(&self) -> Vec<Lockout> {
+        let now = Instant::now();
+        let failures = self.failures.lock().expect("Lockout lock is poisoned");
+        let mut lockouts = failures
+            .iter()
+            .map(|(subject, failures)| {
+                let (ip, username) = match subject {
+                    Subject::Ip(ip) => (Some(*ip), None),
+                    Subject::Username(username) => (None, Some(username.clone())),
+                };
+                Lockout {
+                    ip,
+                    username,
+                    failures: failures.count,
+                    locked: failures.count >= self.config.threshold,
+                    retry_after: failures
+                        .blocked_until
+                        .saturating_duration_since(now)
+                        .as_secs(),
+                }
+            })
+            .collect::<Vec<Lockout>>();
+        lockouts.sort_by_key(|lockout| Reverse(lockout.failures));
+        lockouts
+    }
+
+    /// Clears the failed attempts of a client or a user, or of everyone if neither is specified
+    /// Returns how many entries were removed
+    pub fn clear(&self, ip: Option<IpAddr>, username: Option<&str>) -> usize {
+        let mut failures = self.failures.lock().expect("Lockout lock is poisoned");
+        let before = failures.len();
+        match (ip, username) {
+            (None, None) => failures.clear(),
+            (ip, username) => {
+                for subject in Self::subjects(ip, username) {
+                    failures.remove(&subject);
+                }
+            }
+        }
+        before - failures.len()
+    }
+}
+
+pub async fn list_lockouts(state: Data<State>) -> HttpResponse {
+    HttpResponse::Ok().json(state.lockouts.list())
+}
+
+#[derive(Deserialize)]
+pub struct ClearLockoutQuery {
+    ip: Option<IpAddr>,
+    username: Option<String>,
+}
+
+pub async fn clear_lockouts(query: Query<ClearLockoutQuery>, state: Data<State>) -> HttpResponse {
+    let cleared = state.lockouts.clear(query.ip, query.username.as_deref());
+    info!("Cleared {cleared} lockout(s)");
+    HttpResponse::Ok().json(GenericResponse::success("Successfully cleared lockouts"))
+}
@@ -1,22 +1,58 @@
-use std::future::{ready, Ready};
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+};
 
 use actix_web::{
     body::EitherBody,
     dev::{self, Service, ServiceRequest, ServiceResponse, Transform},
-    web::{Data, Query},
-    Error, HttpResponse,
+    http::{header, Method},
+    web::{self, Data, Query},
+    Error, HttpMessage, HttpRequest, HttpResponse,
 };
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use futures_util::future::LocalBoxFuture;
+use url::form_urlencoded;
+
+use crate::db::token::{self, Scope};
+use crate::db::user::{self, Permission, User};
+use crate::{password, State};
+
+use super::{client_ip, hash_token, GenericResponse};
 
-use crate::{State, User};
+/// The authenticated user of a request and what the request may do
+#[derive(Clone)]
+pub struct Identity {
+    pub user: User,
+    /// Password authentication grants all scopes, API tokens may be restricted to fewer
+    pub scopes: Vec<Scope>,
+    /// Whether the request authenticated using an API token instead of a password
+    pub via_token: bool,
+}
 
-use super::GenericResponse;
+impl Identity {
+    pub fn has_scope(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
 
+    /// Whether the role of the user grants the permission and the scopes allow using it
+    pub fn may(&self, permission: Permission) -> bool {
+        let scope = match permission {
+            Permission::ViewUrls => Scope::Read,
+            Permission::EditUrls => Scope::Write,
+            Permission::ManageAllUrls | Permission::ManageUsers => Scope::Admin,
+        };
+        self.user.role.permissions().contains(&permission) && self.has_scope(scope)
+    }
+}
+
+/// Authenticates requests using an API token or the credentials of a stored user
+/// The authenticated `Identity` is made available to handlers through the request extensions
 pub struct ValidCredentials;
 
 impl<S, B> Transform<S, ServiceRequest> for ValidCredentials
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: 'static,
 {
@@ -27,17 +63,187 @@ where
     type Future = Ready<Result<Self::Transform, Self::InitError>>;
 
     fn new_transform(&self, service: S) -> Self::Future {
-        ready(Ok(ValidCredentialsMiddleware { service }))
+        ready(Ok(ValidCredentialsMiddleware {
+            service: Rc::new(service),
+        }))
     }
 }
 
 pub struct ValidCredentialsMiddleware<S> {
-    service: S,
+    service: Rc<S>,
+}
+
+/// The username and password of a user
+/// Is sent using `Authorization: Basic` or as query parameters, for example `?username=foo&password=bar`
+#[derive(serde::Deserialize)]
+struct Credentials {
+    username: String,
+    password: String,
+}
+
+/// What a request presents in order to authenticate itself
+enum Presented {
+    Token(String),
+    Password(Credentials),
+    /// Credentials were sent as query parameters although this is disabled
+    QueryCredentials,
+    Nothing,
+}
+
+/// Extracts the credentials from the `Authorization` header or, if allowed, from the query parameters
+fn presented_credentials(request: &HttpRequest, allow_query_credentials: bool) -> Presented {
+    let authorization = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().split_once(' '));
+    match authorization {
+        Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") => {
+            return Presented::Token(token.trim().to_string())
+        }
+        Some((scheme, encoded)) if scheme.eq_ignore_ascii_case("basic") => {
+            return BASE64
+                .decode(encoded.trim())
+                .ok()
+                .and_then(|decoded| String::from_utf8(decoded).ok())
+                .and_then(|decoded| {
+                    decoded
+                        .split_once(':')
+                        .map(|(username, password)| Credentials {
+                            username: username.to_string(),
+                            password: password.to_string(),
+                        })
+                })
+                .map_or(Presented::Nothing, Presented::Password)
+        }
+        _ => {}
+    }
+    match Query::<Credentials>::from_query(request.query_string()) {
+        Ok(_) if !allow_query_credentials => Presented::QueryCredentials,
+        Ok(credentials) => Presented::Password(credentials.into_inner()),
+        Err(_) => Presented::Nothing,
+    }
+}
+
+/// Replaces the values of `password` query parameters, so that they do not end up in the logs
+/// Is used by the `Logger` middleware in place of the request line
+pub fn redacted_request_line(request: &ServiceRequest) -> String {
+    let query = request
+        .query_string()
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            // Keys are decoded like `Query<Credentials>` does, so that `passw%6Frd` is caught too
+            Some((key, _))
+                if form_urlencoded::parse(key.as_bytes())
+                    .next()
+                    .is_some_and(|(key, _)| key == "password") =>
+            {
+                format!("{key}=REDACTED")
+            }
+            _ => pair.to_string(),
+        })
+        .collect::<Vec<String>>()
+        .join("&");
+    match query.is_empty() {
+        true => format!(
+            "{} {} {:?}",
+            request.method(),
+            request.path(),
+            request.version()
+        ),
+        false => format!(
+            "{} {}?{query} {:?}",
+            request.method(),
+            request.path(),
+            request.version()
+        ),
+    }
+}
+
+/// Returns the identity belonging to an API token, if it is valid
+async fn authenticate_token(token: &str, state: &State) -> Option<Identity> {
+    match token::use_token(&hash_token(token), &state.db_pool).await {
+        Ok(Some((user, scopes))) => Some(Identity {
+            user,
+            scopes: scopes
+                .iter()
+                .filter_map(|scope| Scope::parse(scope))
+                .collect(),
+            via_token: true,
+        }),
+        Ok(None) => None,
+        Err(err) => {
+            error!("Could not look up API token: {err}");
+            None
+        }
+    }
+}
+
+/// Returns the user matching the credentials, if there is any
+async fn authenticate(credentials: Credentials, state: &State) -> Option<User> {
+    let user = match user::get_user(&credentials.username, &state.db_pool).await {
+        Ok(user) => user,
+        // Unknown users take as long to reject as wrong passwords
+        Err(user::Error::UserDoesNotExist) => {
+            if let Err(err) =
+                web::block(move || password::verify_dummy(&credentials.password)).await
+            {
+                error!("Could not verify password: {err}");
+            }
+            return None;
+        }
+        Err(user::Error::Database(err)) => {
+            error!("Could not look up user `{}`: {err}", credentials.username);
+            return None;
+        }
+        Err(user::Error::UserExists) => unreachable!("Reading a user cannot cause a conflict"),
+    };
+    // Verifying an argon2 hash is expensive, so it must not block the worker
+    let password_hash = user.password_hash.clone();
+    match web::block(move || password::verify(&credentials.password, &password_hash)).await {
+        Ok(true) => Some(user),
+        Ok(false) => None,
+        Err(err) => {
+            error!("Could not verify password: {err}");
+            None
+        }
+    }
+}
+
+/// Authenticates a request outside of the API, for pages which offer more to authenticated users
+/// Failed attempts count towards lockouts just like on the API, but the request is not rejected
+pub async fn identify(request: &HttpRequest, state: &State) -> Option<Identity> {
+    let presented = presented_credentials(request, state.allow_query_credentials);
+    let username = match &presented {
+        Presented::Password(credentials) => Some(credentials.username.clone()),
+        Presented::Token(_) => None,
+        Presented::QueryCredentials | Presented::Nothing => return None,
+    };
+    let ip = client_ip(request, &state.trusted_proxies);
+    if state.lockouts.check(ip, username.as_deref()).is_some() {
+        return None;
+    }
+    let identity = match presented {
+        Presented::Token(token) => authenticate_token(&token, state).await,
+        Presented::Password(credentials) => {
+            authenticate(credentials, state).await.map(|user| Identity {
+                user,
+                scopes: Scope::ALL.to_vec(),
+                via_token: false,
+            })
+        }
+        Presented::QueryCredentials | Presented::Nothing => None,
+    };
+    match &identity {
+        Some(identity) => state.lockouts.record_success(ip, &identity.user.username),
+        None => state.lockouts.record_failure(ip, username.as_deref()),
+    }
+    identity
 }
 
 impl<S, B> Service<ServiceRequest> for ValidCredentialsMiddleware<S>
 where
-    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
     S::Future: 'static,
     B: 'static,
 {
@@ -49,40 +255,200 @@ where
 
     fn call(&self, request: ServiceRequest) -> Self::Future {
         // Attempts to retrieve the app data state
-        let app_data: &Data<State> = request
-            .app_data()
-            .expect("The `ValidCredentials` middleware requires app data to be present");
-
-        // Specify user validity based on query parameters
-        // Attempts to parse a similar query like this `?username=foo&password=bar` into a user
-        let has_valid_credentials = match Query::<User>::from_query(request.query_string()) {
-            Err(_) => false,
-            Ok(user) => {
-                user.username == app_data.user.username && user.password == app_data.user.password
+        let app_data: Data<State> = request
+            .app_data::<Data<State>>()
+            .expect("The `ValidCredentials` middleware requires app data to be present")
+            .clone();
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            // Attempts during a back-off or lockout are rejected without checking their credentials
+            let ip = client_ip(request.request(), &app_data.trusted_proxies);
+            let presented =
+                presented_credentials(request.request(), app_data.allow_query_credentials);
+            let username = match &presented {
+                Presented::Password(credentials) => Some(credentials.username.clone()),
+                _ => None,
+            };
+            if let Some(retry_after) = app_data.lockouts.check(ip, username.as_deref()) {
+                let retry_after = retry_after.as_secs() + 1;
+                let response = HttpResponse::TooManyRequests()
+                    .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+                    .json(GenericResponse::err(
+                        "Too many failed authentication attempts",
+                        "Authentication is temporarily blocked, please retry later",
+                    ))
+                    .map_into_right_body();
+                debug!(
+                    "Rejecting blocked authentication attempt for route `{}`, retry after {retry_after}s",
+                    request.path()
+                );
+                return Ok(ServiceResponse::new(request.into_parts().0, response));
             }
-        };
 
-        // If the user does not have valid credentials, return an error message
-        if !has_valid_credentials {
-            let response = HttpResponse::Forbidden()
-                .json(GenericResponse::err(
-                    "Forbidden",
-                    "You must be authenticated to use the API",
-                ))
-                .map_into_right_body();
-            warn!(
-                "Rejecting invalid authentication for route `{}`",
+            // Authenticates using an API token or the credentials of a user
+            let identity = match presented {
+                Presented::Token(token) => authenticate_token(&token, &app_data).await,
+                Presented::Password(credentials) => {
+                    authenticate(credentials, &app_data)
+                        .await
+                        .map(|user| Identity {
+                            user,
+                            scopes: Scope::ALL.to_vec(),
+                            via_token: false,
+                        })
+                }
+                Presented::QueryCredentials => {
+                    let response = HttpResponse::Forbidden()
+                        .json(GenericResponse::err(
+                            "Forbidden",
+                            "Credentials in the query string are disabled, use the `Authorization` header",
+                        ))
+                        .map_into_right_body();
+                    warn!(
+                        "Rejecting query string credentials for route `{}`",
+                        request.path()
+                    );
+                    return Ok(ServiceResponse::new(request.into_parts().0, response));
+                }
+                // Requests without any credentials are not counted as failed attempts
+                Presented::Nothing => {
+                    let response = HttpResponse::Forbidden()
+                        .json(GenericResponse::err(
+                            "Forbidden",
+                            "You must be authenticated to use the API",
+                        ))
+                        .map_into_right_body();
+                    return Ok(ServiceResponse::new(request.into_parts().0, response));
+                }
+            };
+
+            // If the user does not have valid credentials, return an error message
+            let Some(identity) = identity else {
+                app_data.lockouts.record_failure(ip, username.as_deref());
+                let response = HttpResponse::Forbidden()
+                    .json(GenericResponse::err(
+                        "Forbidden",
+                        "You must be authenticated to use the API",
+                    ))
+                    .map_into_right_body();
+                warn!(
+                    "Rejecting invalid authentication{} from `{}` for route `{}`",
+                    username
+                        .as_deref()
+                        .map(|username| format!(" of `{username}`"))
+                        .unwrap_or_default(),
+                    ip.map(|ip| ip.to_string()).unwrap_or_default(),
+                    request.path()
+                );
+                return Ok(ServiceResponse::new(request.into_parts().0, response));
+            };
+            app_data
+                .lockouts
+                .record_success(ip, &identity.user.username);
+
+            // Reading requires the read scope, everything else requires the write scope
+            let required_scope = match *request.method() {
+                Method::GET | Method::HEAD => Scope::Read,
+                _ => Scope::Write,
+            };
+            if !identity.has_scope(required_scope) {
+                let error = format!(
+                    "This API token lacks the `{}` scope",
+                    required_scope.as_str()
+                );
+                let response = HttpResponse::Forbidden()
+                    .json(GenericResponse::err("Forbidden", &error))
+                    .map_into_right_body();
+                warn!(
+                    "Rejecting API token of `{}` without the `{}` scope for route `{}`",
+                    identity.user.username,
+                    required_scope.as_str(),
+                    request.path()
+                );
+                return Ok(ServiceResponse::new(request.into_parts().0, response));
+            }
+
+            // Forward any valid requests to the original handler
+            trace!(
+                "Accepting valid authentication of `{}` for route `{}`",
+                identity.user.username,
                 request.path()
             );
-            return Box::pin(async { Ok(ServiceResponse::new(request.into_parts().0, response)) });
-        }
+            request.extensions_mut().insert(identity);
+            service
+                .call(request)
+                .await
+                .map(ServiceResponse::map_into_left_body)
+        })
+    }
+}
+
+/// Rejects requests whose identity lacks a permission
+/// Must be used inside of the `ValidCredentials` middleware, for example on individual routes
+pub struct RequirePermission(pub Permission);
+
+impl<S, B> Transform<S, ServiceRequest> for RequirePermission
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequirePermissionMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequirePermissionMiddleware {
+            service,
+            permission: self.0,
+        }))
+    }
+}
+
+pub struct RequirePermissionMiddleware<S> {
+    service: S,
+    permission: Permission,
+}
 
-        // Forward any valid requests to the original handler
-        trace!(
-            "Accepting valid authentication for route `{}`",
-            request.path()
-        );
-        let res = self.service.call(request);
-        Box::pin(async move { res.await.map(ServiceResponse::map_into_left_body) })
+impl<S, B> Service<ServiceRequest> for RequirePermissionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    dev::forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let permitted = request
+            .extensions()
+            .get::<Identity>()
+            .map(|identity| identity.may(self.permission));
+
+        match permitted {
+            Some(true) => {
+                let res = self.service.call(request);
+                Box::pin(async move { res.await.map(ServiceResponse::map_into_left_body) })
+            }
+            // A missing identity means that `ValidCredentials` is not in place, so access is denied
+            _ => {
+                let error = format!("Missing permission `{}`", self.permission.as_str());
+                let response = HttpResponse::Forbidden()
+                    .json(GenericResponse::err("Forbidden", &error))
+                    .map_into_right_body();
+                warn!(
+                    "Rejecting request without permission `{}` for route `{}`",
+                    self.permission.as_str(),
+                    request.path()
+                );
+                Box::pin(async { Ok(ServiceResponse::new(request.into_parts().0, response)) })
+            }
+        }
     }
 }
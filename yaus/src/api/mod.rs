@@ -1,8 +1,26 @@
+mod forward;
+mod lockout;
 mod middleware;
+mod page;
+mod rate_limit;
+mod stats;
+mod tag;
+mod template;
+mod token;
 mod url;
+mod user;
 
-pub use middleware::ValidCredentials;
+pub use lockout::*;
+pub use middleware::{
+    identify, redacted_request_line, Identity, RequirePermission, ValidCredentials,
+};
+pub use page::Pages;
+pub use rate_limit::{client_ip, RateLimit, RateLimiter};
+pub use stats::*;
+pub use tag::*;
+pub use token::*;
 pub use url::*;
+pub use user::*;
 
 #[derive(serde::Serialize)]
 pub struct GenericResponse<'response> {
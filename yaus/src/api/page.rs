@@ -0,0 +1,241 @@
+use std::fs;
+
+use actix_web::http::header::{Accept, ContentType};
+use actix_web::http::StatusCode;
+use actix_web::{mime, HttpMessage, HttpRequest, HttpResponse};
+
+use crate::api::{template, GenericResponse};
+use crate::config::ServerConfig;
+
+/// Escapes text for use in HTML content and quoted attribute values
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            character => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+/// A page which sends the browser to the target, with a link for browsers ignoring the refresh
+pub fn meta_refresh_page(target_url: &str) -> String {
+    let target_url = escape_html(target_url);
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta http-equiv="refresh" content="0; url={target_url}">
+<meta name="robots" content="noindex">
+<title>Redirecting…</title>
+</head>
+<body>
+<p>Redirecting to <a href="{target_url}">{target_url}</a></p>
+</body>
+</html>
+"#
+    )
+}
+
+/// A page which shows the target in a frame, so that the short URL stays in the address bar
+/// Targets which forbid being framed (`X-Frame-Options`, `frame-ancestors`) stay blank
+pub fn frame_page(target_url: &str, title: &str) -> String {
+    let target_url = escape_html(target_url);
+    let title = escape_html(title);
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<style>html, body, iframe {{ margin: 0; border: 0; width: 100%; height: 100%; display: block; }}</style>
+</head>
+<body>
+<iframe src="{target_url}" title="{title}"></iframe>
+</body>
+</html>
+"#
+    )
+}
+
+/// Whether the client prefers an HTML page over JSON, as browsers do
+/// Clients accepting anything, like `curl`, keep receiving JSON
+pub fn prefers_html(request: &HttpRequest) -> bool {
+    let Some(accept) = request.get_header::<Accept>() else {
+        return false;
+    };
+    accept
+        .ranked()
+        .iter()
+        .find_map(|mime| match (mime.type_(), mime.subtype()) {
+            (mime::TEXT, mime::HTML) => Some(true),
+            (mime::APPLICATION, mime::JSON) => Some(false),
+            _ => None,
+        })
+        .unwrap_or(false)
+}
+
+/// What a not found page offers for creating the unknown short id
+pub enum CreateShortcut<'a> {
+    Hidden,
+    /// Links to a Basic authentication challenge, for visitors who are not authenticated yet
+    SignIn,
+    /// A form creating the short URL within the domain, for users who may edit short URLs
+    Form(&'a str),
+}
+
+/// Links to short ids similar to an unknown one, for the `{suggestions}` of not found pages
+/// Depending on `create`, it also offers to create the short URL
+pub fn suggestions(short: &str, suggestions: &[String], create: CreateShortcut) -> String {
+    let title = escape_html(short);
+    let suggestions = if suggestions.is_empty() {
+        String::new()
+    } else {
+        let items = suggestions
+            .iter()
+            .map(|suggestion| {
+                let suggestion = escape_html(suggestion);
+                format!(r#"<li><a href="/{suggestion}">{suggestion}</a></li>"#)
+            })
+            .collect::<String>();
+        format!("<p>Did you mean</p>\n<ul>{items}</ul>\n")
+    };
+    let create = match create {
+        CreateShortcut::Hidden => String::new(),
+        CreateShortcut::SignIn => {
+            format!(
+                r#"<p><a href="/{title}?login">Sign in</a> to create <code>{title}</code></p>
+"#
+            )
+        }
+        CreateShortcut::Form(domain) => {
+            let domain = escape_html(domain);
+            format!(
+                r#"<form id="create" data-short="{title}" data-domain="{domain}">
+<p><label>Create <code>{title}</code> pointing at <input type="url" name="target_url" required></label>
+<button>Create</button></p>
+<p id="error"></p>
+</form>
+<script>
+document.getElementById("create").addEventListener("submit", async (event) => {{
+    event.preventDefault();
+    const form = event.target;
+    const response = await fetch("/api/url", {{
+        method: "POST",
+        // The browser sends the Basic credentials it was challenged for
+        credentials: "same-origin",
+        headers: {{ "Content-Type": "application/json" }},
+        body: JSON.stringify({{
+            domain: form.dataset.domain,
+            short: form.dataset.short,
+            target_url: form.elements.target_url.value,
+        }}),
+    }});
+    if (response.ok) {{
+        location.reload();
+    }} else {{
+        const body = await response.json().catch(() => ({{}}));
+        document.getElementById("error").textContent = body.error || response.statusText;
+    }}
+}});
+</script>
+"#
+            )
+        }
+    };
+    format!("{suggestions}{create}")
+}
+
+/// Is used for errors without a configured template
+const DEFAULT_ERROR_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="robots" content="noindex">
+<title>{status}</title>
+</head>
+<body>
+<h1>{message}</h1>
+<p>{error}</p>
+{suggestions}</body>
+</html>
+"#;
+
+/// The landing page and error templates, which are read from the configured files on startup
+#[derive(Default)]
+pub struct Pages {
+    pub landing: Option<String>,
+    not_found: Option<String>,
+    gone: Option<String>,
+    error: Option<String>,
+}
+
+impl Pages {
+    pub fn load(config: &ServerConfig) -> Result<Self, String> {
+        let read = |path: &Option<String>| {
+            path.as_deref()
+                .map(|path| {
+                    fs::read_to_string(path)
+                        .map_err(|err| format!("Could not read `{path}`: {err}"))
+                })
+                .transpose()
+        };
+        Ok(Self {
+            landing: read(&config.landing_page)?,
+            not_found: read(&config.not_found_page)?,
+            gone: read(&config.gone_page)?,
+            error: read(&config.error_page)?,
+        })
+    }
+
+    /// Renders the template for the status, `suggestions` is HTML and inserted verbatim
+    pub fn render(
+        &self,
+        status: StatusCode,
+        message: &str,
+        error: &str,
+        suggestions: &str,
+    ) -> String {
+        let template = match status {
+            StatusCode::NOT_FOUND => &self.not_found,
+            StatusCode::GONE => &self.gone,
+            _ => &self.error,
+        };
+        let values: [(&str, &str); 4] = [
+            ("status", &escape_html(&status.to_string())),
+            ("message", &escape_html(message)),
+            ("error", &escape_html(error)),
+            ("suggestions", suggestions),
+        ];
+        template::replace_names(template.as_deref().unwrap_or(DEFAULT_ERROR_PAGE), |name| {
+            values
+                .iter()
+                .find(|(placeholder, _)| *placeholder == name)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    /// Replies with an error as JSON or, to browsers, as an HTML page
+    pub fn error_response(
+        &self,
+        request: &HttpRequest,
+        status: StatusCode,
+        message: &str,
+        error: &str,
+    ) -> HttpResponse {
+        if !prefers_html(request) {
+            return HttpResponse::build(status).json(GenericResponse::err(message, error));
+        }
+        HttpResponse::build(status)
+            .content_type(ContentType::html())
+            .body(self.render(status, message, error, ""))
+    }
+}
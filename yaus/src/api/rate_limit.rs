@@ -0,0 +1,186 @@
+use std::{
+    collections::HashMap,
+    future::{ready, Ready},
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    body::EitherBody,
+    dev::{self, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header,
+    web::Data,
+    Error, HttpRequest, HttpResponse,
+};
+use futures_util::future::LocalBoxFuture;
+
+use crate::config::LimitConfig;
+use crate::State;
+
+use super::GenericResponse;
+
+/// How often buckets which have been refilled completely are forgotten
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Determines the IP address of the client which sent a request
+/// `X-Forwarded-For` is only honoured if the request was sent by a trusted proxy,
+/// in that case the right-most address which is not a trusted proxy is used
+pub fn client_ip(request: &HttpRequest, trusted_proxies: &[IpAddr]) -> Option<IpAddr> {
+    let peer = request.peer_addr()?.ip();
+    if !trusted_proxies.contains(&peer) {
+        return Some(peer);
+    }
+    let forwarded_for = request
+        .headers()
+        .get_all("X-Forwarded-For")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|address| address.trim().parse::<IpAddr>().ok())
+        .collect::<Vec<IpAddr>>();
+    Some(
+        forwarded_for
+            .iter()
+            .rev()
+            .find(|address| !trusted_proxies.contains(address))
+            .or_else(|| forwarded_for.first())
+            .copied()
+            .unwrap_or(peer),
+    )
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Tracks a token bucket per client IP address
+/// Each request takes a token, tokens are refilled continuously up to the burst size
+pub struct RateLimiter {
+    config: LimitConfig,
+    buckets: Mutex<(HashMap<IpAddr, Bucket>, Instant)>,
+}
+
+impl RateLimiter {
+    pub fn new(config: LimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new((HashMap::new(), Instant::now())),
+        }
+    }
+
+    /// Takes a token for the client, returns how long to wait if there is none left
+    fn acquire(&self, ip: IpAddr) -> Result<(), Duration> {
+        let now = Instant::now();
+        let burst = self.config.burst as f64;
+        let mut guard = self.buckets.lock().expect("Rate limiter lock is poisoned");
+        let (buckets, pruned_at) = &mut *guard;
+
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: burst,
+            updated_at: now,
+        });
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.config.per_second).min(burst);
+        bucket.updated_at = now;
+
+        let result = if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            // The refill rate is positive, but may be too slow for the wait to be representable
+            Err(
+                Duration::try_from_secs_f64((1.0 - bucket.tokens) / self.config.per_second)
+                    .unwrap_or(Duration::MAX),
+            )
+        };
+
+        // Full buckets carry no information, so they are dropped to bound the memory usage
+        if now.duration_since(*pruned_at) >= PRUNE_INTERVAL {
+            let per_second = self.config.per_second;
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated_at).as_secs_f64() * per_second
+                    < burst
+            });
+            *pruned_at = now;
+        }
+        result
+    }
+}
+
+/// Rejects clients which exceed their rate limit with `429 Too Many Requests`
+/// A limiter with a burst size of 0 lets all requests through
+pub struct RateLimit(pub Arc<RateLimiter>);
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RateLimitMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service,
+            limiter: Arc::clone(&self.0),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: S,
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    dev::forward_ready!(service);
+
+    fn call(&self, request: ServiceRequest) -> Self::Future {
+        let app_data: &Data<State> = request
+            .app_data()
+            .expect("The `RateLimit` middleware requires app data to be present");
+
+        let limited = match client_ip(request.request(), &app_data.trusted_proxies) {
+            Some(ip) if self.limiter.config.burst > 0 => self
+                .limiter
+                .acquire(ip)
+                .err()
+                .map(|retry_after| (ip, retry_after)),
+            _ => None,
+        };
+
+        if let Some((ip, retry_after)) = limited {
+            // `Retry-After` only supports whole seconds
+            let retry_after = retry_after.as_secs().saturating_add(1);
+            debug!(
+                "Rate limiting `{ip}` for route `{}`, retry after {retry_after}s",
+                request.path()
+            );
+            let response = HttpResponse::TooManyRequests()
+                .insert_header((header::RETRY_AFTER, retry_after.to_string()))
+                .json(GenericResponse::err(
+                    "Too many requests",
+                    "The rate limit was exceeded, please retry later",
+                ))
+                .map_into_right_body();
+            return Box::pin(async { Ok(ServiceResponse::new(request.into_parts().0, response)) });
+        }
+
+        let res = self.service.call(request);
+        Box::pin(async move { res.await.map(ServiceResponse::map_into_left_body) })
+    }
+}
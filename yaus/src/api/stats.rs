@@ -0,0 +1,158 @@
+use actix_web::web::{Data, Path, Query};
+use actix_web::HttpResponse;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::api::{find_url, resolve_domain, unknown_domain, GenericResponse};
+use crate::db::stats::{self, Bucket, Rollup};
+use crate::db::url::{Error, Url};
+use crate::db::visit::{self, Visit};
+use crate::State;
+
+/// The maximum amount of buckets a single timeseries request may span
+const MAX_BUCKETS: i64 = 10_000;
+
+/// The default and maximum amount of recent visits included in the statistics of a short URL
+const DEFAULT_VISITS: u32 = 10;
+const MAX_VISITS: u32 = 1000;
+
+#[derive(Deserialize)]
+pub struct StatsQuery {
+    #[serde(default)]
+    domain: String,
+    /// How many of the most recent visits should be included
+    limit: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct UrlStats {
+    short: String,
+    total: i64,
+    visits: Vec<Visit>,
+}
+
+#[derive(Deserialize)]
+pub struct TimeseriesQuery {
+    #[serde(default)]
+    domain: String,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    bucket: Option<Bucket>,
+}
+
+#[derive(Serialize)]
+pub struct Timeseries {
+    short: String,
+    bucket: Bucket,
+    buckets: Vec<Rollup>,
+}
+
+/// Returns an error response if the requested short URL does not exist
+async fn ensure_exists(
+    domain: &str,
+    short: &str,
+    state: &State,
+    error_message: &str,
+) -> Result<Url, HttpResponse> {
+    let Some(domain) = resolve_domain(domain, state) else {
+        return Err(unknown_domain(error_message));
+    };
+    match find_url(&domain, short, state).await {
+        Ok(url) => Ok(url),
+        Err(Error::ShortDoesNotExist) => Err(HttpResponse::UnprocessableEntity().json(
+            GenericResponse::err(error_message, "this shortened url was not found"),
+        )),
+        Err(Error::Database(err)) => {
+            error!("Could not get short URL `{short}`: {err}");
+            Err(HttpResponse::InternalServerError()
+                .json(GenericResponse::err(error_message, "database failure")))
+        }
+        Err(Error::ShortExists | Error::TooManyTags) => {
+            unreachable!("Reading a short URL cannot cause a conflict")
+        }
+    }
+}
+
+pub async fn get_stats(
+    short: Path<String>,
+    query: Query<StatsQuery>,
+    state: Data<State>,
+) -> HttpResponse {
+    let error_message = format!("Cannot get statistics of `{short}`");
+    let url = match ensure_exists(&query.domain, &short, &state, &error_message).await {
+        Ok(url) => url,
+        Err(response) => return response,
+    };
+
+    let total = visit::count_visits(&url.domain, &url.short, &state.db_pool).await;
+    let visits = visit::list_visits(
+        &url.domain,
+        &url.short,
+        &state.db_pool,
+        query.limit.unwrap_or(DEFAULT_VISITS).min(MAX_VISITS) as i64,
+    )
+    .await;
+    match (total, visits) {
+        (Ok(total), Ok(visits)) => HttpResponse::Ok().json(UrlStats {
+            short: url.short,
+            total,
+            visits,
+        }),
+        (Err(err), _) | (_, Err(err)) => {
+            error!("Could not get visits of `{short}`: {err}");
+            HttpResponse::InternalServerError()
+                .json(GenericResponse::err(&error_message, "database failure"))
+        }
+    }
+}
+
+pub async fn get_timeseries(
+    short: Path<String>,
+    query: Query<TimeseriesQuery>,
+    state: Data<State>,
+) -> HttpResponse {
+    let error_message = format!("Cannot get timeseries of `{short}`");
+
+    // Default to the last 48 hours or the last 30 days depending on the bucket size
+    let bucket = query.bucket.unwrap_or(Bucket::Day);
+    let bucket_size = match bucket {
+        Bucket::Hour => Duration::hours(1),
+        Bucket::Day => Duration::days(1),
+    };
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(
+        to - match bucket {
+            Bucket::Hour => Duration::hours(48),
+            Bucket::Day => Duration::days(30),
+        },
+    );
+    if from > to {
+        return HttpResponse::BadRequest().json(GenericResponse::err(
+            &error_message,
+            "`from` must not be later than `to`",
+        ));
+    }
+    if (to - from).num_seconds() / bucket_size.num_seconds() > MAX_BUCKETS {
+        return HttpResponse::BadRequest().json(GenericResponse::err(
+            &error_message,
+            &format!("the requested time range may not span more than {MAX_BUCKETS} buckets"),
+        ));
+    }
+
+    let url = match ensure_exists(&query.domain, &short, &state, &error_message).await {
+        Ok(url) => url,
+        Err(response) => return response,
+    };
+    match stats::get_timeseries(&url.domain, &url.short, bucket, from, to, &state.db_pool).await {
+        Ok(buckets) => HttpResponse::Ok().json(Timeseries {
+            short: url.short,
+            bucket,
+            buckets,
+        }),
+        Err(err) => {
+            error!("Could not get timeseries of `{short}`: {err}");
+            HttpResponse::InternalServerError()
+                .json(GenericResponse::err(&error_message, "database failure"))
+        }
+    }
+}
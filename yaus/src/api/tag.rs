@@ -0,0 +1,40 @@
+use actix_web::web::Data;
+use actix_web::HttpResponse;
+
+use crate::api::GenericResponse;
+use crate::db::tag::{self, MAX_TAGS};
+use crate::State;
+
+/// Trims and lowercases tags, removes duplicates and checks their length
+pub fn normalize_tags(tags: &[String]) -> Result<Vec<String>, &'static str> {
+    let mut normalized = tags
+        .iter()
+        .map(|tag| tag.trim().to_lowercase())
+        .collect::<Vec<String>>();
+    normalized.sort();
+    normalized.dedup();
+
+    if normalized.iter().any(String::is_empty) {
+        return Err("Tags may not be empty");
+    }
+    if normalized.iter().any(|tag| tag.chars().count() > 50) {
+        return Err("Tags may not exceed 50 characters");
+    }
+    if normalized.len() > MAX_TAGS {
+        return Err("A short URL may not carry more than 20 tags");
+    }
+    Ok(normalized)
+}
+
+pub async fn list_tags(state: Data<State>) -> HttpResponse {
+    match tag::list_tags(&state.db_pool).await {
+        Ok(tags) => HttpResponse::Ok().json(tags),
+        Err(err) => {
+            error!("Could not list tags: {err}");
+            HttpResponse::InternalServerError().json(GenericResponse::err(
+                "Could not list tags",
+                "database failure",
+            ))
+        }
+    }
+}
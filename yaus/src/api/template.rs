@@ -0,0 +1,217 @@
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+/// Characters which are escaped in values filled into a template
+/// Only the unreserved characters of RFC 3986 are kept, so that a value cannot alter the structure
+/// of the target URL
+const VALUE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// A placeholder of a target template
+#[derive(Clone, Copy)]
+enum Placeholder {
+    /// `{1}`, `{2}`, …: a single path segment following the short id
+    Segment(usize),
+    /// `{query}`: the entire path following the short id
+    Query,
+}
+
+impl Placeholder {
+    fn name(self) -> String {
+        match self {
+            Self::Segment(index) => index.to_string(),
+            Self::Query => "query".to_string(),
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "query" => Some(Self::Query),
+            _ if name.bytes().all(|byte| byte.is_ascii_digit()) => name
+                .parse()
+                .ok()
+                .filter(|&index| index > 0)
+                .map(Self::Segment),
+            _ => None,
+        }
+    }
+}
+
+/// Replaces `{name}` placeholders in a single pass, so that values cannot introduce further
+/// placeholders
+/// Names for which `value` returns `None` are kept verbatim along with their braces
+pub fn replace_names(template: &str, mut value: impl FnMut(&str) -> Option<String>) -> String {
+    let mut filled = String::with_capacity(template.len());
+    let mut remaining = template;
+    while let Some(start) = remaining.find('{') {
+        filled.push_str(&remaining[..start]);
+        let candidate = &remaining[start..];
+        let replacement = candidate
+            .find('}')
+            .and_then(|end| value(&candidate[1..end]).map(|value| (value, end)));
+        match replacement {
+            Some((replacement, end)) => {
+                filled.push_str(&replacement);
+                remaining = &candidate[end + 1..];
+            }
+            None => {
+                filled.push('{');
+                remaining = &candidate[1..];
+            }
+        }
+    }
+    filled.push_str(remaining);
+    filled
+}
+
+/// Replaces the placeholders of a target template, anything else in braces is kept verbatim
+fn substitute(template: &str, mut value: impl FnMut(Placeholder) -> String) -> String {
+    replace_names(template, |name| Placeholder::parse(name).map(&mut value))
+}
+
+/// Returns whether a target contains placeholders
+pub fn is_template(target: &str) -> bool {
+    let mut found = false;
+    substitute(target, |_| {
+        found = true;
+        String::new()
+    });
+    found
+}
+
+/// Marks the position of a placeholder while a template is normalized as a URL
+/// Consists of characters which are never escaped, so that it survives parsing in any part of a URL
+const SENTINEL: &str = "yausplaceholder";
+
+/// Swaps the placeholders of a template for sentinels, so that it can be normalized as a URL
+/// Returns the masked template and the placeholders to restore using `unmask`
+pub fn mask(template: &str) -> (String, Vec<String>) {
+    let mut placeholders = Vec::new();
+    let masked = substitute(template, |placeholder| {
+        placeholders.push(format!("{{{}}}", placeholder.name()));
+        format!("{SENTINEL}{}x", placeholders.len() - 1)
+    });
+    (masked, placeholders)
+}
+
+/// Whether a part of a masked template contains a placeholder
+pub fn is_masked(text: &str) -> bool {
+    text.contains(SENTINEL)
+}
+
+/// Restores the placeholders of a masked template
+pub fn unmask(masked: &str, placeholders: &[String]) -> String {
+    placeholders
+        .iter()
+        .enumerate()
+        .fold(masked.to_owned(), |unmasked, (index, placeholder)| {
+            unmasked.replacen(&format!("{SENTINEL}{index}x"), placeholder, 1)
+        })
+}
+
+/// Rejects words in braces which look like placeholders but are unknown
+pub fn validate(template: &str) -> Result<(), String> {
+    for (start, _) in template.match_indices('{') {
+        let Some(end) = template[start..].find('}') else {
+            break;
+        };
+        let name = &template[start + 1..start + end];
+        let looks_like_placeholder = !name.is_empty()
+            && name
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_');
+        if looks_like_placeholder && Placeholder::parse(name).is_none() {
+            return Err(format!(
+                "the placeholder `{{{name}}}` is unknown, use `{{query}}` or `{{1}}`, `{{2}}`, …"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Fills the placeholders of a template with the escaped path following the short id
+/// Placeholders without a value are left empty
+pub fn fill(template: &str, rest: Option<&str>) -> String {
+    let rest = rest.unwrap_or_default();
+    let segments = rest
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>();
+    substitute(template, |placeholder| {
+        let value = match placeholder {
+            Placeholder::Segment(index) => segments.get(index - 1).copied().unwrap_or_default(),
+            Placeholder::Query => rest,
+        };
+        utf8_percent_encode(value, VALUE).to_string()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_escapes_segments() {
+        assert_eq!(
+            fill("https://github.com/org/{1}/issues/{2}", Some("yaus/42")),
+            "https://github.com/org/yaus/issues/42"
+        );
+        assert_eq!(
+            fill("https://example.com/{1}", Some("a b?c#d&e")),
+            "https://example.com/a%20b%3Fc%23d%26e"
+        );
+    }
+
+    #[test]
+    fn fill_escapes_query() {
+        assert_eq!(
+            fill(
+                "https://search.example.com/?q={query}",
+                Some("rust async/trait")
+            ),
+            "https://search.example.com/?q=rust%20async%2Ftrait"
+        );
+    }
+
+    #[test]
+    fn fill_leaves_missing_values_empty() {
+        assert_eq!(
+            fill("https://github.com/org/{1}/issues/{2}", None),
+            "https://github.com/org//issues/"
+        );
+        assert_eq!(
+            fill("https://example.com/{2}", Some("a")),
+            "https://example.com/"
+        );
+    }
+
+    #[test]
+    fn fill_keeps_unknown_braces() {
+        assert_eq!(
+            fill("https://example.com/{0}/{foo}/{", Some("a")),
+            "https://example.com/{0}/{foo}/{"
+        );
+    }
+
+    #[test]
+    fn validate_rejects_unknown_placeholders() {
+        assert!(validate("https://example.com/{1}?q={query}").is_ok());
+        assert!(validate("https://example.com/{a-b}/{}").is_ok());
+        assert!(validate("https://example.com/{foo}").is_err());
+        assert!(validate("https://example.com/{0}").is_err());
+    }
+
+    #[test]
+    fn mask_round_trips() {
+        let (masked, placeholders) = mask("https://example.com/{1}/{query}/{1}");
+        assert!(!masked.contains('{'));
+        assert!(is_masked(&masked));
+        assert_eq!(placeholders, ["{1}", "{query}", "{1}"]);
+        assert_eq!(
+            unmask(&masked, &placeholders),
+            "https://example.com/{1}/{query}/{1}"
+        );
+    }
+}
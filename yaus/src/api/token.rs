@@ -0,0 +1,160 @@
+use actix_web::web::{Data, Json, Path, ReqData};
+use actix_web::HttpResponse;
+use chrono::{DateTime, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::api::{GenericResponse, Identity};
+use crate::db::token::{self, ApiToken, Scope};
+use crate::db::user::Permission;
+use crate::State;
+
+/// Makes tokens recognizable, for example in secret scanners
+const TOKEN_PREFIX: &str = "yaus_";
+/// The amount of random characters of a token
+const TOKEN_LENGTH: usize = 40;
+
+/// Generates a new random API token
+fn generate_token() -> String {
+    let random: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(TOKEN_LENGTH)
+        .map(char::from)
+        .collect();
+    format!("{TOKEN_PREFIX}{random}")
+}
+
+/// Hashes an API token for storage and lookup
+/// Tokens are long and random, so a fast hash is sufficient unlike for passwords
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[derive(Deserialize)]
+pub struct NewToken {
+    name: String,
+    /// Defaults to all scopes the creator of the token has
+    scopes: Option<Vec<Scope>>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct CreatedToken {
+    /// The token itself, it cannot be retrieved again later
+    token: String,
+    #[serde(flatten)]
+    details: ApiToken,
+}
+
+pub async fn list_tokens(identity: ReqData<Identity>, state: Data<State>) -> HttpResponse {
+    match token::list_tokens(&identity.user.username, &state.db_pool).await {
+        Ok(tokens) => HttpResponse::Ok().json(tokens),
+        Err(err) => {
+            error!("Could not list API tokens: {err}");
+            HttpResponse::InternalServerError().json(GenericResponse::err(
+                "Could not list API tokens",
+                "database failure",
+            ))
+        }
+    }
+}
+
+pub async fn create_token(
+    body: Json<NewToken>,
+    identity: ReqData<Identity>,
+    state: Data<State>,
+) -> HttpResponse {
+    let error_message = "Could not create API token";
+    // Validate the user's input
+    if body.name.is_empty() || body.name.chars().count() > 100 {
+        return HttpResponse::UnprocessableEntity().json(GenericResponse::err(
+            error_message,
+            "The name must be between 1 and 100 characters long",
+        ));
+    }
+    if matches!(body.expires_at, Some(expires_at) if expires_at <= Utc::now()) {
+        return HttpResponse::UnprocessableEntity().json(GenericResponse::err(
+            error_message,
+            "The expiry date must lie in the future",
+        ));
+    }
+    // A token can never grant more than the identity which creates it
+    let grantable = |scope: &Scope| match scope {
+        Scope::Read => identity.has_scope(Scope::Read),
+        Scope::Write => identity.may(Permission::EditUrls),
+        Scope::Admin => identity.may(Permission::ManageUsers),
+    };
+    let mut scopes = match &body.scopes {
+        Some(scopes) => scopes.clone(),
+        None => Scope::ALL.into_iter().filter(grantable).collect(),
+    };
+    scopes.sort();
+    scopes.dedup();
+    if scopes.is_empty() {
+        return HttpResponse::UnprocessableEntity().json(GenericResponse::err(
+            error_message,
+            "A token requires at least one scope",
+        ));
+    }
+    if let Some(scope) = scopes.iter().find(|scope| !grantable(scope)) {
+        return HttpResponse::Forbidden().json(GenericResponse::err(
+            error_message,
+            &format!("You may not grant the `{}` scope", scope.as_str()),
+        ));
+    }
+
+    let token = generate_token();
+    match token::create_token(
+        &identity.user.username,
+        &body.name,
+        &hash_token(&token),
+        &scopes,
+        body.expires_at,
+        &state.db_pool,
+    )
+    .await
+    {
+        Ok(details) => {
+            info!(
+                "User `{}` created API token `{}` with id {}",
+                details.username, details.name, details.id
+            );
+            HttpResponse::Ok().json(CreatedToken { token, details })
+        }
+        Err(err) => {
+            error!("{error_message}: {err}");
+            HttpResponse::InternalServerError()
+                .json(GenericResponse::err(error_message, "database failure"))
+        }
+    }
+}
+
+pub async fn delete_token(
+    id: Path<i32>,
+    identity: ReqData<Identity>,
+    state: Data<State>,
+) -> HttpResponse {
+    let error_message = "Could not delete API token";
+    match token::delete_token(*id, &identity.user.username, &state.db_pool).await {
+        Ok(true) => {
+            info!(
+                "User `{}` deleted API token with id {id}",
+                identity.user.username
+            );
+            HttpResponse::Ok().json(GenericResponse::success("Successfully deleted API token"))
+        }
+        Ok(false) => HttpResponse::UnprocessableEntity().json(GenericResponse::err(
+            error_message,
+            "This token does not exist",
+        )),
+        Err(err) => {
+            error!("{error_message} {id}: {err}");
+            HttpResponse::InternalServerError()
+                .json(GenericResponse::err(error_message, "database failure"))
+        }
+    }
+}
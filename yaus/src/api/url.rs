@@ -1,51 +1,497 @@
-use actix_web::http::header::{self, HeaderValue};
-use actix_web::web::{Data, Json, Path, Query};
-use actix_web::{get, HttpResponse};
+use actix_web::http::header::{self, ContentType, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::web::{Data, Json, Path, Query, ReqData};
+use actix_web::{routes, HttpRequest, HttpResponse};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
 
-use crate::api::GenericResponse;
-use crate::db::url::{self, Error, Url};
-use crate::{State, User};
+use crate::api::{
+    client_ip, forward, identify, normalize_tags, page, template, GenericResponse, Identity,
+};
+use crate::db::url::{self, Error, NewUrl, RedirectType, Url, UrlFilter, UrlUpdate};
+use crate::db::user::Permission;
+use crate::db::visit::{self, NewVisit};
+use crate::short_id;
+use crate::State;
 
-pub async fn create_url(body: Json<Url>, _: Query<User>, state: Data<State>) -> HttpResponse {
+/// Identifies the domain of a short URL, omitting it selects the default domain
+#[derive(Deserialize)]
+pub struct DomainQuery {
+    #[serde(default)]
+    pub domain: String,
+}
+
+/// Lowercases a host name and strips its port as well as a trailing dot
+pub fn normalize_domain(host: &str) -> String {
+    let host = host.trim();
+    // IPv6 addresses are enclosed in brackets as they contain colons themselves
+    let host = match host.strip_prefix('[') {
+        Some(rest) => match rest.split_once(']') {
+            Some((address, _)) => &host[..address.len() + 2],
+            None => host,
+        },
+        None => host.split(':').next().unwrap_or(host),
+    };
+    host.trim_end_matches('.').to_lowercase()
+}
+
+/// Returns the configured domain matching the `Host` of a request
+/// Requests for any other host use the default domain, which is empty
+fn request_domain(request: &HttpRequest, state: &State) -> String {
+    let host = request
+        .headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .or_else(|| request.uri().host())
+        .map(normalize_domain)
+        .unwrap_or_default();
+    match state.domains.contains(&host) {
+        true => host,
+        false => String::new(),
+    }
+}
+
+/// Normalizes a domain specified through the API, returns `None` if it is not configured
+pub fn resolve_domain(domain: &str, state: &State) -> Option<String> {
+    let domain = normalize_domain(domain);
+    (domain.is_empty() || state.domains.contains(&domain)).then_some(domain)
+}
+
+pub(crate) fn unknown_domain(error_message: &str) -> HttpResponse {
+    HttpResponse::UnprocessableEntity().json(GenericResponse::err(
+        error_message,
+        "This domain is not configured",
+    ))
+}
+
+/// Whether the scheme is on the allow-list of target URLs
+fn is_allowed_scheme(scheme: &str, state: &State) -> bool {
+    state
+        .target
+        .allowed_schemes
+        .iter()
+        .any(|allowed| allowed.eq_ignore_ascii_case(scheme))
+}
+
+/// Parses a target or fallback URL and returns its normalized form
+/// Internationalized host names are converted to punycode
+fn normalize_target(
+    name: &str,
+    target: &str,
+    request: &HttpRequest,
+    state: &State,
+) -> Result<String, String> {
+    let target = target.trim();
+    template::validate(target).map_err(|err| format!("The {name} URL is invalid: {err}"))?;
+    // Templates are normalized with their placeholders swapped for sentinels
+    let (target, placeholders) = template::mask(target);
+    let target =
+        ::url::Url::parse(&target).map_err(|err| format!("The {name} URL is invalid: {err}"))?;
+    if template::is_masked(target.scheme()) || target.host_str().is_some_and(template::is_masked) {
+        return Err(format!(
+            "The {name} URL may only contain placeholders after its host"
+        ));
+    }
+    if !is_allowed_scheme(target.scheme(), state) {
+        return Err(format!(
+            "The scheme `{}` is not allowed for the {name} URL, allowed are `{}`",
+            target.scheme(),
+            state.target.allowed_schemes.join("`, `")
+        ));
+    }
+    if let Some(host) = target.host_str() {
+        state
+            .domain_filter
+            .check(host)
+            .map_err(|err| format!("The {name} URL is not allowed: {err}"))?;
+    }
+    // Short URLs pointing at the shortener could redirect in circles
+    if let Some(host) = target.host_str().map(normalize_domain) {
+        let request_host = request.connection_info().host().to_string();
+        if host == normalize_domain(&request_host) || state.domains.contains(&host) {
+            return Err(format!(
+                "The {name} URL may not point at the shortener itself"
+            ));
+        }
+    }
+    Ok(template::unmask(target.as_str(), &placeholders))
+}
+
+/// Checks the length of the optional descriptive fields of a short URL
+fn validate_metadata(title: Option<&str>, description: Option<&str>) -> Result<(), &'static str> {
+    if matches!(title, Some(title) if title.chars().count() > 200) {
+        return Err("The title may not exceed 200 characters");
+    }
+    if matches!(description, Some(description) if description.chars().count() > 2000) {
+        return Err("The description may not exceed 2000 characters");
+    }
+    Ok(())
+}
+
+/// Looks up a short URL addressed through the API using its normalized short id
+/// Falls back to the exact short id, so that short ids stored before they became case-insensitive
+/// can still be managed
+pub async fn find_url(domain: &str, short: &str, state: &State) -> url::Result<Url> {
+    let normalized = short_id::normalize(short, &state.short_id);
+    match url::get_url(domain, &normalized, &state.db_pool).await {
+        Err(Error::ShortDoesNotExist) if normalized != short => {
+            url::get_url(domain, short, &state.db_pool).await
+        }
+        result => result,
+    }
+}
+
+/// Returns an error response unless the user may modify the short URL
+/// Users may only manage the short URLs they created unless they may manage all short URLs
+/// Otherwise returns the stored short URL
+async fn ensure_may_manage(
+    domain: &str,
+    short: &str,
+    identity: &Identity,
+    state: &State,
+    error_message: &str,
+) -> Result<Url, HttpResponse> {
+    let url = match find_url(domain, short, state).await {
+        Ok(url) => url,
+        Err(Error::ShortDoesNotExist) => {
+            return Err(
+                HttpResponse::UnprocessableEntity().json(GenericResponse::err(
+                    error_message,
+                    "This short id does not exist",
+                )),
+            )
+        }
+        Err(err) => {
+            error!("{error_message} `{short}`: {err:?}");
+            return Err(HttpResponse::InternalServerError()
+                .json(GenericResponse::err(error_message, "Database failure")));
+        }
+    };
+    if !identity.may(Permission::ManageAllUrls)
+        && url.created_by.as_deref() != Some(identity.user.username.as_str())
+    {
+        warn!(
+            "User `{}` attempted to modify the short URL `{short}` of another user",
+            identity.user.username
+        );
+        return Err(HttpResponse::Forbidden().json(GenericResponse::err(
+            error_message,
+            "You may only manage your own short URLs",
+        )));
+    }
+    Ok(url)
+}
+
+pub async fn create_url(
+    mut body: Json<NewUrl>,
+    request: HttpRequest,
+    identity: ReqData<Identity>,
+    state: Data<State>,
+) -> HttpResponse {
     // Validate the user's input
-    if body.short.len() > 20 {
+    if let Some(short) = &body.short {
+        body.short = match short_id::validate(short, &state.short_id) {
+            Ok(short) => Some(short),
+            Err(err) => {
+                return HttpResponse::BadRequest()
+                    .json(GenericResponse::err("Could not create short url", &err))
+            }
+        };
+    }
+    body.target_url = match normalize_target("target", &body.target_url, &request, &state) {
+        Ok(target_url) => target_url,
+        Err(err) => {
+            return HttpResponse::BadRequest()
+                .json(GenericResponse::err("Could not create short url", &err))
+        }
+    };
+    if let Some(fallback_url) = &body.fallback_url {
+        body.fallback_url = match normalize_target("fallback", fallback_url, &request, &state) {
+            Ok(fallback_url) => Some(fallback_url),
+            Err(err) => {
+                return HttpResponse::BadRequest()
+                    .json(GenericResponse::err("Could not create short url", &err))
+            }
+        };
+    }
+    if body.target_url.len() > 500 {
         return HttpResponse::PayloadTooLarge().json(GenericResponse::err(
             "Could not create short url",
-            "The short ID may not exceed 20 characters",
+            "The target URL may not exceed 500 characters",
         ));
     };
-    if body.target_url.len() > 500 {
+    if matches!(&body.fallback_url, Some(fallback_url) if fallback_url.len() > 500) {
         return HttpResponse::PayloadTooLarge().json(GenericResponse::err(
             "Could not create short url",
-            "The target URL may not exceed 500 characters",
+            "The fallback URL may not exceed 500 characters",
         ));
     };
-    // Create the URL in the database
-    match url::create_url(&body, &state.db_pool).await {
-        Ok(_) => {
+    body.domain = match resolve_domain(&body.domain, &state) {
+        Some(domain) => domain,
+        None => return unknown_domain("Could not create short url"),
+    };
+    if matches!(body.max_visits, Some(max_visits) if max_visits < 1) {
+        return HttpResponse::UnprocessableEntity().json(GenericResponse::err(
+            "Could not create short url",
+            "The maximum amount of visits must be at least 1",
+        ));
+    };
+    if let Err(err) = validate_metadata(body.title.as_deref(), body.description.as_deref()) {
+        return HttpResponse::PayloadTooLarge()
+            .json(GenericResponse::err("Could not create short url", err));
+    };
+    body.tags = match normalize_tags(&body.tags) {
+        Ok(tags) => tags,
+        Err(err) => {
+            return HttpResponse::UnprocessableEntity()
+                .json(GenericResponse::err("Could not create short url", err))
+        }
+    };
+    if body.is_expired() {
+        return HttpResponse::UnprocessableEntity().json(GenericResponse::err(
+            "Could not create short url",
+            "The expiry date must lie in the future",
+        ));
+    };
+    // Create the URL in the database, generated short ids are retried on collision
+    let generate_short = body.short.is_none();
+    let mut short = body.short.clone().unwrap_or_default();
+    let mut attempts = 0;
+    let result = loop {
+        attempts += 1;
+        if generate_short {
+            let generated = match short_id::generate(&state.short_id, &state.db_pool).await {
+                Ok(generated) => generated,
+                Err(err) => break Err(Error::Database(err)),
+            };
+            short = match short_id::validate(&generated, &state.short_id) {
+                Ok(short) => short,
+                Err(err) if attempts < state.short_id.max_attempts => {
+                    debug!("Generated short id `{generated}` is invalid, retrying: {err}");
+                    continue;
+                }
+                // Reported like a collision, as another attempt might succeed
+                Err(_) => break Err(Error::ShortExists),
+            };
+        }
+        match url::create_url(&short, &body, &identity.user.username, &state.db_pool).await {
+            Err(Error::ShortExists) if generate_short && attempts < state.short_id.max_attempts => {
+                debug!("Generated short id `{short}` is already taken, retrying")
+            }
+            result => break result,
+        }
+    };
+    match result {
+        Ok(url) => {
             info!(
-                "Created redirect from `{}` to `{}`",
-                body.short, body.target_url
+                "Created redirect from `{}/{}` to `{}`",
+                url.domain, url.short, url.target_url
             );
-            HttpResponse::Ok().json(GenericResponse::success("Successfully created short URL"))
+            HttpResponse::Ok().json(url)
         }
         Err(err) => {
             let error_message = "Could not create short URL";
             match err {
+                Error::ShortExists if generate_short => {
+                    warn!("Could not generate an unused short id after {attempts} attempt(s)");
+                    HttpResponse::ServiceUnavailable().json(GenericResponse::err(
+                        error_message,
+                        "Could not generate an unused short id, please try again",
+                    ))
+                }
                 Error::ShortExists => HttpResponse::UnprocessableEntity().json(
                     GenericResponse::err(error_message, "This short id is already taken"),
                 ),
-                _ => HttpResponse::InternalServerError()
-                    .json(GenericResponse::err(error_message, "Database failure")),
+                _ => {
+                    error!("{error_message} `{short}`: {err:?}");
+                    HttpResponse::InternalServerError()
+                        .json(GenericResponse::err(error_message, "Database failure"))
+                }
             }
         }
     }
 }
 
-pub async fn delete_url(to_delete: Path<String>, state: Data<State>) -> HttpResponse {
-    match url::delete_url(&to_delete, &state.db_pool).await {
+pub async fn update_url(
+    to_update: Path<String>,
+    query: Query<DomainQuery>,
+    mut body: Json<UrlUpdate>,
+    request: HttpRequest,
+    identity: ReqData<Identity>,
+    state: Data<State>,
+) -> HttpResponse {
+    // Validate the user's input
+    if let Some(short) = &body.short {
+        body.short = match short_id::validate(short, &state.short_id) {
+            Ok(short) => Some(short),
+            Err(err) => {
+                return HttpResponse::BadRequest()
+                    .json(GenericResponse::err("Could not update short url", &err))
+            }
+        };
+    }
+    if let Some(target_url) = &body.target_url {
+        body.target_url = match normalize_target("target", target_url, &request, &state) {
+            Ok(target_url) => Some(target_url),
+            Err(err) => {
+                return HttpResponse::BadRequest()
+                    .json(GenericResponse::err("Could not update short url", &err))
+            }
+        };
+    }
+    if let Some(Some(fallback_url)) = &body.fallback_url {
+        body.fallback_url = match normalize_target("fallback", fallback_url, &request, &state) {
+            Ok(fallback_url) => Some(Some(fallback_url)),
+            Err(err) => {
+                return HttpResponse::BadRequest()
+                    .json(GenericResponse::err("Could not update short url", &err))
+            }
+        };
+    }
+    if matches!(&body.target_url, Some(target_url) if target_url.len() > 500) {
+        return HttpResponse::PayloadTooLarge().json(GenericResponse::err(
+            "Could not update short url",
+            "The target URL may not exceed 500 characters",
+        ));
+    };
+    if matches!(&body.fallback_url, Some(Some(fallback_url)) if fallback_url.len() > 500) {
+        return HttpResponse::PayloadTooLarge().json(GenericResponse::err(
+            "Could not update short url",
+            "The fallback URL may not exceed 500 characters",
+        ));
+    };
+    if matches!(body.max_visits, Some(Some(max_visits)) if max_visits < 1) {
+        return HttpResponse::UnprocessableEntity().json(GenericResponse::err(
+            "Could not update short url",
+            "The maximum amount of visits must be at least 1",
+        ));
+    };
+    if matches!(body.expires_at, Some(Some(expires_at)) if expires_at <= Utc::now()) {
+        return HttpResponse::UnprocessableEntity().json(GenericResponse::err(
+            "Could not update short url",
+            "The expiry date must lie in the future",
+        ));
+    };
+    if let Err(err) = validate_metadata(
+        body.title.as_ref().and_then(Option::as_deref),
+        body.description.as_ref().and_then(Option::as_deref),
+    ) {
+        return HttpResponse::PayloadTooLarge()
+            .json(GenericResponse::err("Could not update short url", err));
+    };
+    let update = &mut *body;
+    for tags in [
+        &mut update.tags,
+        &mut update.add_tags,
+        &mut update.remove_tags,
+    ] {
+        if let Some(to_normalize) = tags {
+            *tags = match normalize_tags(to_normalize) {
+                Ok(normalized) => Some(normalized),
+                Err(err) => {
+                    return HttpResponse::UnprocessableEntity()
+                        .json(GenericResponse::err("Could not update short url", err))
+                }
+            };
+        }
+    }
+    let Some(domain) = resolve_domain(&query.domain, &state) else {
+        return unknown_domain("Could not update URL");
+    };
+    let current = match ensure_may_manage(
+        &domain,
+        &to_update,
+        &identity,
+        &state,
+        "Could not update URL",
+    )
+    .await
+    {
+        Ok(url) => url,
+        Err(response) => return response,
+    };
+    let to_update = current.short;
+    // Changing the target or fallback decides again whether the short URL is disabled, new values
+    // have been checked already, but the ones which are kept may still be blocked
+    let disabled_reason = (body.target_url.is_some() || body.fallback_url.is_some()).then(|| {
+        let target_reason = match &body.target_url {
+            Some(_) => None,
+            None => state.domain_filter.check_url(&current.target_url).err(),
+        };
+        let fallback_reason = match &body.fallback_url {
+            Some(_) => None,
+            None => current
+                .fallback_url
+                .as_deref()
+                .and_then(|fallback_url| state.domain_filter.check_url(fallback_url).err()),
+        };
+        target_reason.or(fallback_reason)
+    });
+    match url::update_url(
+        &domain,
+        &to_update,
+        &body,
+        disabled_reason.as_ref().map(Option::as_deref),
+        &state.db_pool,
+    )
+    .await
+    {
+        Ok(url) => {
+            info!(
+                "Updated redirect `{domain}/{to_update}` to `{}/{}` -> `{}`",
+                url.domain, url.short, url.target_url
+            );
+            HttpResponse::Ok().json(url)
+        }
+        Err(err) => {
+            let error_message = "Could not update URL";
+            match err {
+                Error::ShortDoesNotExist => HttpResponse::UnprocessableEntity().json(
+                    GenericResponse::err(error_message, "This short id does not exist"),
+                ),
+                Error::ShortExists => HttpResponse::UnprocessableEntity().json(
+                    GenericResponse::err(error_message, "The new short id is already taken"),
+                ),
+                Error::TooManyTags => {
+                    HttpResponse::UnprocessableEntity().json(GenericResponse::err(
+                        error_message,
+                        "A short URL may not carry more than 20 tags",
+                    ))
+                }
+                Error::Database(err) => {
+                    error!("{error_message} `{to_update}`: {err}");
+                    HttpResponse::InternalServerError()
+                        .json(GenericResponse::err(error_message, "Database failure"))
+                }
+            }
+        }
+    }
+}
+
+pub async fn delete_url(
+    to_delete: Path<String>,
+    query: Query<DomainQuery>,
+    identity: ReqData<Identity>,
+    state: Data<State>,
+) -> HttpResponse {
+    let Some(domain) = resolve_domain(&query.domain, &state) else {
+        return unknown_domain("Could not delete URL");
+    };
+    let to_delete = match ensure_may_manage(
+        &domain,
+        &to_delete,
+        &identity,
+        &state,
+        "Could not delete URL",
+    )
+    .await
+    {
+        Ok(url) => url.short,
+        Err(response) => return response,
+    };
+    match url::delete_url(&domain, &to_delete, &state.db_pool).await {
         Ok(_) => {
-            info!("Deleted redirect `{to_delete}`");
+            info!("Deleted redirect `{domain}/{to_delete}`");
             HttpResponse::Ok().json(GenericResponse::success("Successfully deleted URL"))
         }
         Err(err) => {
@@ -54,59 +500,466 @@ pub async fn delete_url(to_delete: Path<String>, state: Data<State>) -> HttpResp
                 Error::ShortDoesNotExist => HttpResponse::UnprocessableEntity().json(
                     GenericResponse::err(error_message, "This short id does not exist"),
                 ),
-                _ => HttpResponse::InternalServerError()
-                    .json(GenericResponse::err(error_message, "Database failure")),
+                _ => {
+                    error!("{error_message} `{to_delete}`: {err:?}");
+                    HttpResponse::InternalServerError()
+                        .json(GenericResponse::err(error_message, "Database failure"))
+                }
             }
         }
     }
 }
 
-pub async fn get_target(requested_resource: Path<String>, state: Data<State>) -> HttpResponse {
+pub async fn get_target(
+    requested_resource: Path<String>,
+    query: Query<DomainQuery>,
+    state: Data<State>,
+) -> HttpResponse {
+    let error_message = format!("Cannot get target URL of `{requested_resource}`");
+    let Some(domain) = resolve_domain(&query.domain, &state) else {
+        return unknown_domain(&error_message);
+    };
     // Fetch the target URL from the database
-    match url::get_url(&requested_resource, &state.db_pool).await {
+    match find_url(&domain, &requested_resource, &state).await {
         Ok(url) => HttpResponse::Ok().json(url),
         Err(_) => HttpResponse::UnprocessableEntity().json(GenericResponse::err(
-            &format!("Cannot get target URL of `{requested_resource}`"),
+            &error_message,
             "this shortened url was not found",
         )),
     }
 }
 
-pub async fn list_urls(limit: Path<u32>, state: Data<State>) -> HttpResponse {
-    match url::list_urls(&state.db_pool, limit.to_owned() as i64).await {
-        Ok(urls) => HttpResponse::Ok().json(urls),
-        Err(_) => HttpResponse::InternalServerError().json(GenericResponse::err(
-            "Could not list URLs",
-            "database failure",
-        )),
+/// The default and maximum amount of short URLs per page
+const DEFAULT_PAGE_SIZE: u32 = 100;
+const MAX_PAGE_SIZE: u32 = 1000;
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Short,
+    Created,
+}
+
+#[derive(Deserialize)]
+pub struct ListQuery {
+    #[serde(default)]
+    domain: String,
+    /// Continues a previous listing, taken from its `next_cursor`
+    cursor: Option<String>,
+    limit: Option<u32>,
+    #[serde(default)]
+    sort: SortOrder,
+    prefix: Option<String>,
+    /// Searches the target URLs for this text
+    q: Option<String>,
+    tag: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct UrlPage {
+    urls: Vec<Url>,
+    /// Is omitted on the last page
+    next_cursor: Option<String>,
+    /// The amount of short URLs matching the filter across all pages
+    total: i64,
+}
+
+/// Encodes the position of a short URL in a listing into an opaque cursor
+fn encode_cursor(sort: SortOrder, url: &Url) -> String {
+    let position = match sort {
+        SortOrder::Short => url.short.clone(),
+        SortOrder::Created => format!(
+            "{}|{}",
+            url.created_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+            url.short
+        ),
+    };
+    position.bytes().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Decodes a cursor created by `encode_cursor`
+fn decode_cursor(cursor: &str) -> Option<String> {
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(cursor.get(index..index + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+pub async fn list_urls(query: Query<ListQuery>, state: Data<State>) -> HttpResponse {
+    let error_message = "Could not list URLs";
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let tag = query.tag.as_deref().map(|tag| tag.trim().to_lowercase());
+    let Some(domain) = resolve_domain(&query.domain, &state) else {
+        return unknown_domain(error_message);
+    };
+    let filter = UrlFilter {
+        domain: &domain,
+        prefix: query.prefix.as_deref(),
+        search: query.q.as_deref(),
+        tag: tag.as_deref(),
+    };
+    let after = match query.cursor.as_deref().map(decode_cursor) {
+        None => None,
+        Some(Some(position)) => Some(position),
+        Some(None) => {
+            return HttpResponse::BadRequest()
+                .json(GenericResponse::err(error_message, "invalid cursor"))
+        }
+    };
+
+    // One additional short URL is fetched in order to know whether there is a next page
+    let urls = match (query.sort, after.as_deref()) {
+        (SortOrder::Short, after) => {
+            url::list_urls_by_short(&state.db_pool, &filter, after, limit as i64 + 1).await
+        }
+        (SortOrder::Created, None) => {
+            url::list_urls_by_creation(&state.db_pool, &filter, None, limit as i64 + 1).await
+        }
+        (SortOrder::Created, Some(after)) => {
+            let after = after.split_once('|').and_then(|(created_at, short)| {
+                Some((DateTime::parse_from_rfc3339(created_at).ok()?, short))
+            });
+            let Some((created_at, short)) = after else {
+                return HttpResponse::BadRequest()
+                    .json(GenericResponse::err(error_message, "invalid cursor"));
+            };
+            url::list_urls_by_creation(
+                &state.db_pool,
+                &filter,
+                Some((created_at.with_timezone(&Utc), short)),
+                limit as i64 + 1,
+            )
+            .await
+        }
+    };
+    let total = url::count_urls(&state.db_pool, &filter).await;
+
+    match (urls, total) {
+        (Ok(mut urls), Ok(total)) => {
+            let next_cursor = match urls.len() > limit as usize {
+                true => {
+                    urls.truncate(limit as usize);
+                    urls.last().map(|url| encode_cursor(query.sort, url))
+                }
+                false => None,
+            };
+            HttpResponse::Ok().json(UrlPage {
+                urls,
+                next_cursor,
+                total,
+            })
+        }
+        (Err(err), _) | (_, Err(err)) => {
+            error!("{error_message}: {err:?}");
+            HttpResponse::InternalServerError()
+                .json(GenericResponse::err(error_message, "database failure"))
+        }
     }
 }
 
+/// Records a visit of a short URL, failing to do so should not prevent the redirect
+async fn record_visit(request: &HttpRequest, url: &Url, state: &State) {
+    let headers = request.headers();
+    let ip = client_ip(request, &state.trusted_proxies).map(|ip| ip.to_string());
+    if let Err(err) = visit::record_visit(
+        &NewVisit {
+            domain: &url.domain,
+            short: &url.short,
+            referrer: headers
+                .get(header::REFERER)
+                .and_then(|value| value.to_str().ok()),
+            user_agent: headers
+                .get(header::USER_AGENT)
+                .and_then(|value| value.to_str().ok()),
+            ip: ip.as_deref(),
+        },
+        &state.db_pool,
+    )
+    .await
+    {
+        error!(
+            "Could not record visit of `{}/{}`: {err}",
+            url.domain, url.short
+        );
+    }
+}
+
+/// Suggests similar short ids to browsers visiting an unknown one
+/// Users who may edit short URLs are offered to create it, anonymous visitors to sign in first
+async fn not_found_page(
+    domain: &str,
+    short: &str,
+    request: &HttpRequest,
+    state: &State,
+) -> HttpResponse {
+    // More candidates than shown are fetched, as the trigram similarity only approximates the
+    // edit distance
+    let mut suggestions = url::list_similar_shorts(domain, short, 20, &state.db_pool)
+        .await
+        .unwrap_or_else(|err| {
+            error!("Could not look up short ids similar to `{short}`: {err:?}");
+            Vec::new()
+        });
+    suggestions.sort_by_cached_key(|suggestion| {
+        (
+            short_id::edit_distance(short, suggestion),
+            !suggestion.starts_with(short),
+        )
+    });
+    suggestions.truncate(5);
+    let create = match short_id::validate(short, &state.short_id) {
+        Ok(_) => match identify(request, state).await {
+            Some(identity) if identity.may(Permission::EditUrls) => {
+                page::CreateShortcut::Form(domain)
+            }
+            Some(_) => page::CreateShortcut::Hidden,
+            // Browsers only send Basic credentials after being challenged for them
+            None if ::url::form_urlencoded::parse(request.query_string().as_bytes())
+                .any(|(key, _)| key == "login") =>
+            {
+                return HttpResponse::Unauthorized()
+                    .insert_header((header::WWW_AUTHENTICATE, r#"Basic realm="yaus""#))
+                    .content_type(ContentType::html())
+                    .body(state.pages.render(
+                        StatusCode::UNAUTHORIZED,
+                        &format!("Cannot create resource `{short}`"),
+                        "authentication is required",
+                        "",
+                    ))
+            }
+            None => page::CreateShortcut::SignIn,
+        },
+        Err(_) => page::CreateShortcut::Hidden,
+    };
+    HttpResponse::NotFound()
+        .content_type(ContentType::html())
+        .body(state.pages.render(
+            StatusCode::NOT_FOUND,
+            &format!("Cannot redirect to resource `{short}`"),
+            "this shortened url was not found",
+            &page::suggestions(short, &suggestions, create),
+        ))
+}
+
+/// Redirects visitors of `/` or shows them the landing page, if either is configured
+pub async fn handle_root(request: HttpRequest, state: Data<State>) -> HttpResponse {
+    if let Some(root_redirect) = &state.root_redirect {
+        return HttpResponse::TemporaryRedirect()
+            .append_header((header::LOCATION, root_redirect.as_str()))
+            .finish();
+    }
+    match &state.pages.landing {
+        Some(landing) => HttpResponse::Ok()
+            .content_type(ContentType::html())
+            .body(landing.clone()),
+        None => handle_not_found(request, state).await,
+    }
+}
+
+/// Answers requests which no route matches
+pub async fn handle_not_found(request: HttpRequest, state: Data<State>) -> HttpResponse {
+    state.pages.error_response(
+        &request,
+        StatusCode::NOT_FOUND,
+        &format!("Cannot find `{}`", request.path()),
+        "this page does not exist",
+    )
+}
+
+/// Why a visit-limited short URL does not redirect to its target anymore
+const MAX_VISITS_REACHED: &str = "this shortened url has reached its maximum amount of visits";
+
+/// Builds the URL a visit is sent to along with its `Location` header
+/// Fails with the reason if the target cannot be redirected to
+fn resolve_target(
+    target_url: &str,
+    rest: Option<&str>,
+    url: &Url,
+    redirect_type: RedirectType,
+    request: &HttpRequest,
+    state: &State,
+) -> Result<(String, HeaderValue), String> {
+    // The path following the short id is used up by the placeholders of a template
+    let (target_url, rest) = if template::is_template(target_url) {
+        (template::fill(target_url, rest), None)
+    } else {
+        (target_url.to_owned(), rest.filter(|_| url.forward_path))
+    };
+    let target_url = forward::forward(
+        &target_url,
+        rest,
+        Some(request.query_string()).filter(|_| url.forward_query),
+        state.query_conflict,
+    )
+    .map_err(|err| format!("invalid target URL: {err}"))?;
+    let location =
+        HeaderValue::from_str(&target_url).map_err(|err| format!("invalid target URL: {err}"))?;
+    // Pages put the target on the shortener's own origin, so targets stored before schemes were
+    // restricted must not run scripts there
+    if matches!(
+        redirect_type,
+        RedirectType::MetaRefresh | RedirectType::Frame
+    ) && !::url::Url::parse(&target_url)
+        .is_ok_and(|target| is_allowed_scheme(target.scheme(), state))
+    {
+        return Err("the scheme of the target URL is not allowed".to_string());
+    }
+    Ok((target_url, location))
+}
+
+/// The short id of a visit and the path following it
+#[derive(Deserialize)]
+pub struct RedirectPath {
+    short: String,
+    rest: Option<String>,
+}
+
+#[routes]
 #[get("/{short}")]
-pub async fn handle_redirect(requested_resource: Path<String>, state: Data<State>) -> HttpResponse {
-    // Fetch the target URL from the database
-    let url = match url::get_url(&requested_resource, &state.db_pool).await {
+#[get("/{short}/{rest:.*}")]
+pub async fn handle_redirect(
+    requested_resource: Path<RedirectPath>,
+    request: HttpRequest,
+    state: Data<State>,
+) -> HttpResponse {
+    let RedirectPath { short, rest } = requested_resource.into_inner();
+    // Short ids are looked up within the domain of the requested host
+    let domain = request_domain(&request, &state);
+    let requested_resource = short_id::normalize(&short, &state.short_id);
+    let url = match url::get_url(&domain, &requested_resource, &state.db_pool).await {
         Ok(url) => url,
-        Err(_) => {
-            return HttpResponse::NotFound().json(GenericResponse::err(
+        Err(Error::ShortDoesNotExist) if page::prefers_html(&request) => {
+            return not_found_page(&domain, &requested_resource, &request, &state).await
+        }
+        Err(Error::ShortDoesNotExist) => {
+            return state.pages.error_response(
+                &request,
+                StatusCode::NOT_FOUND,
                 &format!("Cannot redirect to resource `{requested_resource}`"),
                 "this shortened url was not found",
-            ))
+            )
+        }
+        Err(err) => {
+            error!("Could not look up `{requested_resource}`: {err:?}");
+            return state.pages.error_response(
+                &request,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &format!("Cannot redirect to resource `{requested_resource}`"),
+                "database failure",
+            );
         }
     };
+    // A trailing path is only accepted by short URLs which pass it on or fill it into a template
+    if rest.as_deref().is_some_and(|rest| !rest.is_empty())
+        && !url.forward_path
+        && !template::is_template(&url.target_url)
+    {
+        return state.pages.error_response(
+            &request,
+            StatusCode::NOT_FOUND,
+            &format!("Cannot redirect to resource `{requested_resource}`"),
+            "this shortened url does not forward paths",
+        );
+    }
+    // Disabled short URLs do not even send the visitor to their fallback
+    if let Some(reason) = &url.disabled_reason {
+        return state.pages.error_response(
+            &request,
+            StatusCode::GONE,
+            &format!("Cannot redirect to resource `{requested_resource}`"),
+            &format!("this shortened url has been disabled: {reason}"),
+        );
+    }
+    // Expired or used up short URLs either send the visitor to their fallback or are gone
+    let mut inactive_reason = if url.is_expired() {
+        Some("this shortened url has expired")
+    } else if url.max_visits.is_some_and(|max_visits| max_visits <= 0) {
+        Some(MAX_VISITS_REACHED)
+    } else {
+        None
+    };
+    let redirect_type = url.redirect_type.unwrap_or(state.default_redirect_type);
+    let (target_url, location) = loop {
+        let target_url = match (inactive_reason, &url.fallback_url) {
+            (None, _) => &url.target_url,
+            (Some(_), Some(fallback_url)) => fallback_url,
+            (Some(reason), None) => {
+                return state.pages.error_response(
+                    &request,
+                    StatusCode::GONE,
+                    &format!("Cannot redirect to resource `{requested_resource}`"),
+                    reason,
+                )
+            }
+        };
+        let target = match resolve_target(
+            target_url,
+            rest.as_deref(),
+            &url,
+            redirect_type,
+            &request,
+            &state,
+        ) {
+            Ok(target) => target,
+            Err(err) => {
+                return state.pages.error_response(
+                    &request,
+                    StatusCode::UNPROCESSABLE_ENTITY,
+                    &format!("Cannot redirect to resource `{requested_resource}`"),
+                    &err,
+                )
+            }
+        };
+        // A visit is only used up once the redirect is certain to be sent
+        if inactive_reason.is_some() || url.max_visits.is_none() {
+            break target;
+        }
+        match url::consume_visit(&url.domain, &url.short, &state.db_pool).await {
+            Ok(true) => break target,
+            // Concurrent visits used up the remaining ones, so the fallback is tried instead
+            Ok(false) => inactive_reason = Some(MAX_VISITS_REACHED),
+            Err(err) => {
+                error!("Could not consume visit of `{}`: {err:?}", url.short);
+                return state.pages.error_response(
+                    &request,
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    &format!("Cannot redirect to resource `{requested_resource}`"),
+                    "database failure",
+                );
+            }
+        }
+    };
+    // Only visits which reach the actual target are counted
+    if inactive_reason.is_none() {
+        record_visit(&request, &url, &state).await;
+    }
     // Send the redirect target URL to the client
-    HttpResponse::TemporaryRedirect()
-        .append_header((
-            header::LOCATION,
-            match HeaderValue::from_str(&url.target_url) {
-                Ok(header) => header,
-                Err(err) => {
-                    return HttpResponse::UnprocessableEntity().json(GenericResponse::err(
-                        "Cannot redirect to resource `{path}`",
-                        &format!("invalid target URL: {err}"),
-                    ))
-                }
-            },
-        ))
-        .json(url)
+    let mut response = match redirect_type {
+        RedirectType::MovedPermanently => HttpResponse::MovedPermanently(),
+        RedirectType::Found => HttpResponse::Found(),
+        RedirectType::TemporaryRedirect => HttpResponse::TemporaryRedirect(),
+        RedirectType::PermanentRedirect => HttpResponse::PermanentRedirect(),
+        RedirectType::MetaRefresh => {
+            return HttpResponse::Ok()
+                .content_type(ContentType::html())
+                .body(page::meta_refresh_page(&target_url))
+        }
+        RedirectType::Frame => {
+            return HttpResponse::Ok()
+                .content_type(ContentType::html())
+                .body(page::frame_page(
+                    &target_url,
+                    url.title.as_deref().unwrap_or(&url.short),
+                ))
+        }
+    };
+    // The details of the short URL are only available through the API
+    response
+        .append_header((header::LOCATION, location))
+        .finish()
 }
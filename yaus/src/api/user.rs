@@ -0,0 +1,208 @@
+use actix_web::web::{self, Data, Json, Path, ReqData};
+use actix_web::HttpResponse;
+use serde::Deserialize;
+
+use crate::api::{GenericResponse, Identity};
+use crate::db::token::Scope;
+use crate::db::user::{self, Error, Permission, Role};
+use crate::{password, State};
+
+#[derive(Deserialize)]
+pub struct NewUser {
+    username: String,
+    password: String,
+    #[serde(default = "default_role")]
+    role: Role,
+}
+
+fn default_role() -> Role {
+    Role::Editor
+}
+
+/// Describes changes to an existing user, omitted fields are left unchanged
+#[derive(Deserialize)]
+pub struct UserUpdate {
+    password: Option<String>,
+    /// May only be changed by users who may manage users
+    role: Option<Role>,
+}
+
+/// Checks the length of a new password
+fn validate_password(password: &str) -> Result<(), &'static str> {
+    match password.chars().count() {
+        length if length < 8 => Err("The password must be at least 8 characters long"),
+        length if length > 200 => Err("The password may not exceed 200 characters"),
+        _ => Ok(()),
+    }
+}
+
+/// Hashes a password on the blocking thread pool as argon2 is expensive by design
+async fn hash_password(password: String) -> Result<String, HttpResponse> {
+    let error = match web::block(move || password::hash(&password)).await {
+        Ok(Ok(hash)) => return Ok(hash),
+        Ok(Err(err)) => err.to_string(),
+        Err(err) => err.to_string(),
+    };
+    error!("Could not hash password: {error}");
+    Err(
+        HttpResponse::InternalServerError().json(GenericResponse::err(
+            "Could not hash password",
+            "internal failure",
+        )),
+    )
+}
+
+pub async fn list_users(state: Data<State>) -> HttpResponse {
+    let error_message = "Could not list users";
+    match user::list_users(&state.db_pool).await {
+        Ok(users) => HttpResponse::Ok().json(users),
+        Err(err) => {
+            error!("{error_message}: {err}");
+            HttpResponse::InternalServerError()
+                .json(GenericResponse::err(error_message, "database failure"))
+        }
+    }
+}
+
+pub async fn create_user(
+    body: Json<NewUser>,
+    identity: ReqData<Identity>,
+    state: Data<State>,
+) -> HttpResponse {
+    let error_message = "Could not create user";
+    // Validate the user's input
+    if body.username.is_empty() || body.username.len() > 100 {
+        return HttpResponse::UnprocessableEntity().json(GenericResponse::err(
+            error_message,
+            "The username must be between 1 and 100 characters long",
+        ));
+    }
+    if let Err(err) = validate_password(&body.password) {
+        return HttpResponse::UnprocessableEntity().json(GenericResponse::err(error_message, err));
+    }
+    let password_hash = match hash_password(body.password.clone()).await {
+        Ok(hash) => hash,
+        Err(response) => return response,
+    };
+    match user::create_user(&body.username, &password_hash, body.role, &state.db_pool).await {
+        Ok(user) => {
+            info!(
+                "User `{}` created user `{}` with role {:?}",
+                identity.user.username, user.username, user.role
+            );
+            HttpResponse::Ok().json(user)
+        }
+        Err(Error::UserExists) => HttpResponse::UnprocessableEntity().json(GenericResponse::err(
+            error_message,
+            "This username is already taken",
+        )),
+        Err(Error::Database(err)) => {
+            error!("{error_message} `{}`: {err}", body.username);
+            HttpResponse::InternalServerError()
+                .json(GenericResponse::err(error_message, "database failure"))
+        }
+        Err(Error::UserDoesNotExist) => unreachable!("Creating a user cannot require it to exist"),
+    }
+}
+
+pub async fn update_user(
+    username: Path<String>,
+    body: Json<UserUpdate>,
+    identity: ReqData<Identity>,
+    state: Data<State>,
+) -> HttpResponse {
+    let error_message = "Could not update user";
+    // Users may change their own password, everything else requires the permission to manage users
+    if (*username != identity.user.username || body.role.is_some())
+        && !identity.may(Permission::ManageUsers)
+    {
+        return HttpResponse::Forbidden().json(GenericResponse::err(
+            error_message,
+            &format!("Missing permission `{}`", Permission::ManageUsers.as_str()),
+        ));
+    }
+    // Otherwise a token could set a password and sign in with it to gain all scopes
+    if body.password.is_some() && identity.via_token && !identity.has_scope(Scope::Admin) {
+        return HttpResponse::Forbidden().json(GenericResponse::err(
+            error_message,
+            "Changing a password requires password authentication or the `admin` scope",
+        ));
+    }
+    if *username == identity.user.username && matches!(body.role, Some(role) if role != Role::Admin)
+    {
+        return HttpResponse::UnprocessableEntity().json(GenericResponse::err(
+            error_message,
+            "Admins cannot revoke their own privileges",
+        ));
+    }
+    let password_hash = match &body.password {
+        Some(password) => {
+            if let Err(err) = validate_password(password) {
+                return HttpResponse::UnprocessableEntity()
+                    .json(GenericResponse::err(error_message, err));
+            }
+            match hash_password(password.clone()).await {
+                Ok(hash) => Some(hash),
+                Err(response) => return response,
+            }
+        }
+        None => None,
+    };
+    match user::update_user(
+        &username,
+        password_hash.as_deref(),
+        body.role,
+        &state.db_pool,
+    )
+    .await
+    {
+        Ok(user) => {
+            info!(
+                "User `{}` updated user `{username}`",
+                identity.user.username
+            );
+            HttpResponse::Ok().json(user)
+        }
+        Err(Error::UserDoesNotExist) => HttpResponse::UnprocessableEntity().json(
+            GenericResponse::err(error_message, "This user does not exist"),
+        ),
+        Err(Error::Database(err)) => {
+            error!("{error_message} `{username}`: {err}");
+            HttpResponse::InternalServerError()
+                .json(GenericResponse::err(error_message, "database failure"))
+        }
+        Err(Error::UserExists) => unreachable!("Modifying a user cannot cause a conflict"),
+    }
+}
+
+pub async fn delete_user(
+    username: Path<String>,
+    identity: ReqData<Identity>,
+    state: Data<State>,
+) -> HttpResponse {
+    let error_message = "Could not delete user";
+    if *username == identity.user.username {
+        return HttpResponse::UnprocessableEntity().json(GenericResponse::err(
+            error_message,
+            "Users cannot delete themselves",
+        ));
+    }
+    match user::delete_user(&username, &state.db_pool).await {
+        Ok(_) => {
+            info!(
+                "User `{}` deleted user `{username}`",
+                identity.user.username
+            );
+            HttpResponse::Ok().json(GenericResponse::success("Successfully deleted user"))
+        }
+        Err(Error::UserDoesNotExist) => HttpResponse::UnprocessableEntity().json(
+            GenericResponse::err(error_message, "This user does not exist"),
+        ),
+        Err(Error::Database(err)) => {
+            error!("{error_message} `{username}`: {err}");
+            HttpResponse::InternalServerError()
+                .json(GenericResponse::err(error_message, "database failure"))
+        }
+        Err(Error::UserExists) => unreachable!("Modifying a user cannot cause a conflict"),
+    }
+}
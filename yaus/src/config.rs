@@ -2,11 +2,14 @@ use std::{
     env,
     fs::{self, File},
     io::{self, Write},
+    net::IpAddr,
     path::Path,
 };
 
 use serde::Deserialize;
 
+use crate::db::url::RedirectType;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 pub enum Error {
@@ -26,45 +29,98 @@ impl From<toml::de::Error> for Error {
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 pub struct Config {
     pub server: ServerConfig,
     pub user: User,
     pub database: DatabaseConfig,
-}
-
-impl Default for Config {
-    fn default() -> Self {
-        Self {
-            server: ServerConfig::default(),
-            user: User::default(),
-            database: DatabaseConfig::default(),
-        }
-    }
+    #[serde(default)]
+    pub stats: StatsConfig,
+    #[serde(default)]
+    pub expiry: ExpiryConfig,
+    #[serde(default)]
+    pub short_id: ShortIdConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    #[serde(default)]
+    pub lockout: LockoutConfig,
+    #[serde(default)]
+    pub domains: Vec<DomainConfig>,
+    #[serde(default)]
+    pub target: TargetConfig,
+    #[serde(default)]
+    pub passthrough: PassthroughConfig,
 }
 
 #[derive(Deserialize)]
 pub struct ServerConfig {
     pub port: u16,
+    /// Whether the API accepts credentials as query parameters besides the `Authorization` header
+    #[serde(default = "default_allow_query_credentials")]
+    pub allow_query_credentials: bool,
+    /// Reverse proxies whose `X-Forwarded-For` header is trusted to name the client
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
+    /// How short URLs without their own redirect type send visitors to their target
+    #[serde(default = "default_redirect_type")]
+    pub default_redirect_type: RedirectType,
+    /// Where visitors of `/` are redirected to, takes precedence over `landing_page`
+    #[serde(default)]
+    pub root_redirect: Option<String>,
+    /// An HTML file shown to visitors of `/`
+    #[serde(default)]
+    pub landing_page: Option<String>,
+    /// HTML templates shown to browsers instead of JSON errors for unknown (404), expired or
+    /// disabled (410) short URLs and any other errors
+    #[serde(default)]
+    pub not_found_page: Option<String>,
+    #[serde(default)]
+    pub gone_page: Option<String>,
+    #[serde(default)]
+    pub error_page: Option<String>,
+}
+
+fn default_redirect_type() -> RedirectType {
+    RedirectType::TemporaryRedirect
+}
+
+fn default_allow_query_credentials() -> bool {
+    true
 }
 
 impl Default for ServerConfig {
     fn default() -> Self {
-        Self { port: 8080 }
+        Self {
+            port: 8080,
+            allow_query_credentials: default_allow_query_credentials(),
+            trusted_proxies: vec![],
+            default_redirect_type: default_redirect_type(),
+            root_redirect: None,
+            landing_page: None,
+            not_found_page: None,
+            gone_page: None,
+            error_page: None,
+        }
     }
 }
 
 #[derive(Clone, Deserialize)]
 pub struct User {
     pub username: String,
-    pub password: String,
+    /// The plain password, `password_hash` keeps it out of the configuration instead
+    #[serde(default)]
+    pub password: Option<String>,
+    /// An argon2 (PHC string) or bcrypt hash of the password, takes precedence over `password`
+    #[serde(default)]
+    pub password_hash: Option<String>,
 }
 
 impl Default for User {
     fn default() -> Self {
         Self {
             username: "admin".to_string(),
-            password: "admin".to_string(),
+            password: Some("admin".to_string()),
+            password_hash: None,
         }
     }
 }
@@ -90,19 +146,236 @@ impl Default for DatabaseConfig {
     }
 }
 
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct StatsConfig {
+    /// How often (in seconds) recorded visits are compacted into hourly and daily rollups
+    pub rollup_interval: u64,
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            rollup_interval: 60,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpiryAction {
+    /// Expired short URLs are deleted permanently
+    Delete,
+    /// Expired short URLs are moved into the `url_archive` table
+    Archive,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ExpiryConfig {
+    /// How often (in seconds) expired short URLs are swept
+    pub sweep_interval: u64,
+    /// How long (in seconds) an expired short URL is kept before it is swept
+    pub grace_period: u64,
+    /// What happens to expired short URLs once the grace period has passed
+    pub action: ExpiryAction,
+}
+
+impl Default for ExpiryConfig {
+    fn default() -> Self {
+        Self {
+            sweep_interval: 300,
+            grace_period: 7 * 24 * 60 * 60,
+            action: ExpiryAction::Archive,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ShortIdStrategy {
+    /// Random base62 characters, for example `x3Fq9a`
+    Random,
+    /// A counter encoded in base62, for example `1c`
+    Sequential,
+    /// A readable adjective-noun pair, for example `brave-otter`
+    Words,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ShortIdConfig {
+    /// How short ids are generated if a new short URL does not specify one
+    pub strategy: ShortIdStrategy,
+    /// The length of randomly generated short ids
+    pub length: usize,
+    /// How often generating a short id is attempted before giving up
+    pub max_attempts: u32,
+    /// The characters short ids may consist of, `a-z` denotes a range of characters
+    pub allowed_characters: String,
+    /// Short ids which may not be used, `api` is always reserved as it is shadowed by the API
+    pub reserved: Vec<String>,
+    /// Whether short ids are stored in lowercase and visitors may use any case
+    pub case_insensitive: bool,
+}
+
+impl Default for ShortIdConfig {
+    fn default() -> Self {
+        Self {
+            strategy: ShortIdStrategy::Random,
+            length: 6,
+            max_attempts: 10,
+            allowed_characters: "a-zA-Z0-9_-".to_string(),
+            reserved: ["api", "favicon.ico", "robots.txt", "health"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+            case_insensitive: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct LimitConfig {
+    /// How many requests a client may send at once, 0 disables the limit
+    pub burst: u32,
+    /// How many requests per second a client may send in the long run, must be positive
+    pub per_second: f64,
+}
+
+impl LimitConfig {
+    /// Enabled limits need a positive refill rate, otherwise clients would be blocked for good
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        if self.burst > 0 && !(self.per_second.is_finite() && self.per_second > 0.0) {
+            return Err(format!(
+                "`per_second` must be a positive number, but is {}",
+                self.per_second
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Applies to visits of short URLs
+    pub redirect: LimitConfig,
+    /// Applies to the `/api` scope
+    pub api: LimitConfig,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            redirect: LimitConfig {
+                burst: 30,
+                per_second: 5.0,
+            },
+            api: LimitConfig {
+                burst: 60,
+                per_second: 10.0,
+            },
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct LockoutConfig {
+    /// After this many consecutive failed attempts, a client IP or username is locked out, 0 disables this
+    pub threshold: u32,
+    /// How long (in seconds) further attempts are delayed after the first failure, doubles with every failure
+    pub base_delay: u64,
+    /// How long (in seconds) a lockout lasts
+    pub duration: u64,
+}
+
+impl Default for LockoutConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 10,
+            base_delay: 1,
+            duration: 15 * 60,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct TargetConfig {
+    /// The schemes target and fallback URLs may use
+    pub allowed_schemes: Vec<String>,
+    /// Target domains which are refused, `*.example.com` matches all subdomains of `example.com`
+    pub blocked_domains: Vec<String>,
+    /// If not empty, only target domains matching one of these patterns are accepted
+    pub allowed_domains: Vec<String>,
+    /// A file containing further blocked domain patterns, one per line
+    pub blocked_domains_file: Option<String>,
+    /// A file containing further allowed domain patterns, one per line
+    pub allowed_domains_file: Option<String>,
+    /// How often (in seconds) the files are reloaded and existing short URLs are checked again
+    pub rescan_interval: u64,
+}
+
+impl Default for TargetConfig {
+    fn default() -> Self {
+        Self {
+            allowed_schemes: vec!["http".to_string(), "https".to_string()],
+            blocked_domains: vec![],
+            allowed_domains: vec![],
+            blocked_domains_file: None,
+            allowed_domains_file: None,
+            rescan_interval: 300,
+        }
+    }
+}
+
+/// Decides which value is kept if the target and a visit contain the same query parameter
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum QueryConflict {
+    /// The parameter of the visit replaces the one of the target
+    Request,
+    /// The parameter of the target is kept and the one of the visit is dropped
+    Target,
+    /// Both parameters are passed on
+    Append,
+}
+
+#[derive(Deserialize)]
+pub struct PassthroughConfig {
+    /// Applies to short URLs which forward the query string of visits
+    pub query_conflict: QueryConflict,
+}
+
+impl Default for PassthroughConfig {
+    fn default() -> Self {
+        Self {
+            query_conflict: QueryConflict::Request,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct DomainConfig {
+    /// The host name which serves its own set of short URLs, for example `go.example.com`
+    pub name: String,
+}
+
 pub fn read_config(file_path: &str) -> Result<Config> {
     // Create or read the file based on it's current existence
     let path = Path::new(file_path);
     match &path.exists() {
         true => {
             // Read the file
-            let raw_config = fs::read_to_string(&path)?;
+            let raw_config = fs::read_to_string(path)?;
             debug!("Found existing config file at {file_path}");
             Ok::<Config, Error>(toml::from_str::<Config>(&raw_config)?)
         }
         false => {
             // Create the file and it's parent directories
-            fs::create_dir_all(&path.parent().unwrap())?;
+            fs::create_dir_all(path.parent().unwrap())?;
             let mut file = File::create(path)?;
             file.write_all(include_bytes!("default_config.toml"))?;
             info!("Created new config file at {file_path}");
@@ -123,6 +396,36 @@ impl Config {
             }
         }
 
+        if let Ok(allow) = env::var("YAUS_ALLOW_QUERY_CREDENTIALS") {
+            if let Ok(parsed_allow) = allow.parse::<bool>() {
+                debug!("Selected `YAUS_ALLOW_QUERY_CREDENTIALS` over value from config file");
+                self.server.allow_query_credentials = parsed_allow;
+            }
+        }
+
+        if let Ok(proxies) = env::var("YAUS_TRUSTED_PROXIES") {
+            if let Ok(parsed_proxies) = proxies
+                .split(',')
+                .filter(|proxy| !proxy.trim().is_empty())
+                .map(|proxy| proxy.trim().parse::<IpAddr>())
+                .collect::<std::result::Result<Vec<IpAddr>, _>>()
+            {
+                debug!("Selected `YAUS_TRUSTED_PROXIES` over value from config file");
+                self.server.trusted_proxies = parsed_proxies;
+            }
+        }
+
+        if let Ok(domains) = env::var("YAUS_DOMAINS") {
+            debug!("Selected `YAUS_DOMAINS` over value from config file");
+            self.domains = domains
+                .split(',')
+                .filter(|domain| !domain.trim().is_empty())
+                .map(|domain| DomainConfig {
+                    name: domain.trim().to_string(),
+                })
+                .collect();
+        }
+
         // User configuration
         if let Ok(username) = env::var("YAUS_USERNAME") {
             debug!("Selected `YAUS_USERNAME` over value from config file");
@@ -130,7 +433,13 @@ impl Config {
         }
         if let Ok(password) = env::var("YAUS_PASSWORD") {
             debug!("Selected `YAUS_PASSWORD` over value from config file");
-            self.user.password = password
+            self.user.password = Some(password);
+            self.user.password_hash = None;
+        }
+        if let Ok(password_hash) = env::var("YAUS_PASSWORD_HASH") {
+            debug!("Selected `YAUS_PASSWORD_HASH` over value from config file");
+            self.user.password_hash = Some(password_hash);
+            self.user.password = None;
         }
 
         // Database configuration
@@ -156,5 +465,32 @@ impl Config {
             debug!("Selected `YAUS_DB_DATABASE` over value from config file");
             self.database.database = db_database
         }
+
+        // Statistics configuration
+        if let Ok(interval) = env::var("YAUS_ROLLUP_INTERVAL") {
+            if let Ok(parsed_interval) = interval.parse::<u64>() {
+                debug!("Selected `YAUS_ROLLUP_INTERVAL` over value from config file");
+                self.stats.rollup_interval = parsed_interval;
+            }
+        }
+
+        // Expiry configuration
+        if let Ok(grace_period) = env::var("YAUS_EXPIRY_GRACE_PERIOD") {
+            if let Ok(parsed_grace_period) = grace_period.parse::<u64>() {
+                debug!("Selected `YAUS_EXPIRY_GRACE_PERIOD` over value from config file");
+                self.expiry.grace_period = parsed_grace_period;
+            }
+        }
+        if let Ok(action) = env::var("YAUS_EXPIRY_ACTION") {
+            let parsed_action = match action.as_str() {
+                "delete" => Some(ExpiryAction::Delete),
+                "archive" => Some(ExpiryAction::Archive),
+                _ => None,
+            };
+            if let Some(parsed_action) = parsed_action {
+                debug!("Selected `YAUS_EXPIRY_ACTION` over value from config file");
+                self.expiry.action = parsed_action;
+            }
+        }
     }
 }
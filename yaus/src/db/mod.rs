@@ -2,7 +2,12 @@ use sqlx::{postgres::PgPoolOptions, PgPool};
 
 use crate::config::DatabaseConfig;
 
+pub mod stats;
+pub mod tag;
+pub mod token;
 pub mod url;
+pub mod user;
+pub mod visit;
 
 pub async fn connect(config: &DatabaseConfig) -> Result<PgPool, sqlx::Error> {
     debug!("Initializing database pool...");
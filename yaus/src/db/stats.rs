@@ -0,0 +1,148 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+/// The granularity of a visit rollup
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+    Hour,
+    Day,
+}
+
+impl Bucket {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Bucket::Hour => "hour",
+            Bucket::Day => "day",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Rollup {
+    pub start: DateTime<Utc>,
+    pub visits: i64,
+}
+
+pub type Result<T> = std::result::Result<T, sqlx::Error>;
+
+/// Visits younger than this amount of seconds are not compacted yet
+/// This leaves concurrent redirects enough time to commit their visit
+const COMPACTION_DELAY_SECONDS: f64 = 10.0;
+
+/// Adds all visits which have been recorded since the last compaction to the rollup counters
+/// Returns the number of compacted visits
+pub async fn compact_visits(pool: &PgPool) -> Result<i64> {
+    let mut transaction = pool.begin().await?;
+
+    // Lock the compaction state so that concurrent instances cannot count visits twice
+    let state = sqlx::query!(
+        r#"
+        SELECT
+            rolled_up_until,
+            now() - make_interval(secs => $1) AS "compact_until!"
+        FROM visit_rollup_state
+        FOR UPDATE
+        "#,
+        COMPACTION_DELAY_SECONDS,
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+
+    let compacted = sqlx::query_scalar!(
+        r#"
+        SELECT
+            COUNT(*) AS "count!"
+        FROM visit
+        WHERE visited_at >= $1
+        AND visited_at < $2
+        "#,
+        state.rolled_up_until,
+        state.compact_until,
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+
+    for bucket in [Bucket::Hour, Bucket::Day] {
+        sqlx::query!(
+            r#"
+            INSERT INTO
+            visit_rollup(
+                domain,
+                short,
+                bucket,
+                bucket_start,
+                visits
+            )
+            SELECT
+                domain,
+                short,
+                $1::TEXT,
+                date_trunc($1::TEXT, visited_at, 'UTC'),
+                COUNT(*)
+            FROM visit
+            WHERE visited_at >= $2
+            AND visited_at < $3
+            GROUP BY domain, short, date_trunc($1::TEXT, visited_at, 'UTC')
+            ON CONFLICT (domain, short, bucket, bucket_start)
+            DO UPDATE SET visits = visit_rollup.visits + EXCLUDED.visits
+            "#,
+            bucket.as_str(),
+            state.rolled_up_until,
+            state.compact_until,
+        )
+        .execute(&mut transaction)
+        .await?;
+    }
+
+    sqlx::query!(
+        r#"
+        UPDATE visit_rollup_state
+        SET rolled_up_until=$1
+        "#,
+        state.compact_until,
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+    Ok(compacted)
+}
+
+/// Returns one rollup per bucket in the specified time range, including empty buckets
+pub async fn get_timeseries(
+    domain: &str,
+    short: &str,
+    bucket: Bucket,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    pool: &PgPool,
+) -> Result<Vec<Rollup>> {
+    sqlx::query_as!(
+        Rollup,
+        r#"
+        SELECT
+            series.start AS "start!",
+            COALESCE(visit_rollup.visits, 0) AS "visits!"
+        FROM generate_series(
+            date_trunc($2, $3::TIMESTAMPTZ, 'UTC'),
+            $4::TIMESTAMPTZ,
+            make_interval(hours => CASE WHEN $2 = 'hour' THEN 1 ELSE 24 END)
+        ) AS series(start)
+        LEFT JOIN visit_rollup
+            ON visit_rollup.domain=$5
+            AND visit_rollup.short=$1
+            AND visit_rollup.bucket=$2
+            AND visit_rollup.bucket_start=series.start
+        ORDER BY series.start
+        "#,
+        short,
+        bucket.as_str(),
+        from,
+        to,
+        domain,
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,104 @@
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, Transaction};
+
+#[derive(Debug, Serialize)]
+pub struct Tag {
+    pub tag: String,
+    /// How many short URLs carry this tag
+    pub urls: i64,
+}
+
+pub type Result<T> = std::result::Result<T, sqlx::Error>;
+
+/// The maximum amount of tags a single short URL may carry
+pub const MAX_TAGS: usize = 20;
+
+/// Replaces all tags of a short URL
+pub async fn set_tags(
+    domain: &str,
+    short: &str,
+    tags: &[String],
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        DELETE FROM
+        url_tag
+        WHERE domain=$1
+        AND short=$2
+        "#,
+        domain,
+        short,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    add_tags(domain, short, tags, transaction).await
+}
+
+/// Attaches tags to a short URL, tags it already carries are skipped
+pub async fn add_tags(
+    domain: &str,
+    short: &str,
+    tags: &[String],
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO
+        url_tag(
+            domain,
+            short,
+            tag
+        )
+        SELECT $1, $2, UNNEST($3::VARCHAR[])
+        ON CONFLICT (domain, short, tag) DO NOTHING
+        "#,
+        domain,
+        short,
+        tags,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    Ok(())
+}
+
+/// Detaches tags from a short URL, tags it does not carry are ignored
+pub async fn remove_tags(
+    domain: &str,
+    short: &str,
+    tags: &[String],
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        DELETE FROM
+        url_tag
+        WHERE domain=$1
+        AND short=$2
+        AND tag=ANY($3::VARCHAR[])
+        "#,
+        domain,
+        short,
+        tags,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    Ok(())
+}
+
+/// Lists all tags which are in use
+pub async fn list_tags(pool: &PgPool) -> Result<Vec<Tag>> {
+    sqlx::query_as!(
+        Tag,
+        r#"
+        SELECT
+            tag,
+            COUNT(*) AS "urls!"
+        FROM url_tag
+        GROUP BY tag
+        ORDER BY tag
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,164 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+use super::user::{Role, User};
+
+/// What an API token may be used for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    /// Reading short URLs, statistics and tags
+    Read,
+    /// Creating, modifying and deleting short URLs and tokens
+    Write,
+    /// Managing users and the short URLs of others, only effective for admins
+    Admin,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 3] = [Scope::Read, Scope::Write, Scope::Admin];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Write => "write",
+            Scope::Admin => "admin",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|known| known.as_str() == scope)
+    }
+}
+
+/// Describes an API token, the token itself is only known when it is created
+#[derive(Debug, Serialize)]
+pub struct ApiToken {
+    pub id: i32,
+    pub username: String,
+    /// Helps users to recognize their tokens
+    pub name: String,
+    pub scopes: Vec<String>,
+    /// After this point in time, the token is no longer accepted
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+pub type Result<T> = std::result::Result<T, sqlx::Error>;
+
+pub async fn create_token(
+    username: &str,
+    name: &str,
+    token_hash: &str,
+    scopes: &[Scope],
+    expires_at: Option<DateTime<Utc>>,
+    pool: &PgPool,
+) -> Result<ApiToken> {
+    let scopes = scopes
+        .iter()
+        .map(|scope| scope.as_str().to_string())
+        .collect::<Vec<String>>();
+    sqlx::query_as!(
+        ApiToken,
+        r#"
+        INSERT INTO
+        api_token(
+            username,
+            name,
+            token_hash,
+            scopes,
+            expires_at
+        )
+        VALUES($1, $2, $3, $4, $5)
+        RETURNING id, username, name, scopes, expires_at, created_at, last_used_at
+        "#,
+        username,
+        name,
+        token_hash,
+        &scopes,
+        expires_at,
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Looks up the user and the scopes of an unexpired token and records its usage
+pub async fn use_token(token_hash: &str, pool: &PgPool) -> Result<Option<(User, Vec<String>)>> {
+    let row = sqlx::query!(
+        r#"
+        WITH used AS (
+            UPDATE api_token
+            SET last_used_at=now()
+            WHERE token_hash=$1
+            AND (expires_at IS NULL OR expires_at > now())
+            RETURNING username, scopes
+        )
+        SELECT
+            users.username,
+            users.password_hash,
+            users.role AS "role: Role",
+            users.created_at,
+            used.scopes
+        FROM used
+        JOIN users ON users.username=used.username
+        "#,
+        token_hash,
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|row| {
+        (
+            User {
+                username: row.username,
+                password_hash: row.password_hash,
+                role: row.role,
+                created_at: row.created_at,
+            },
+            row.scopes,
+        )
+    }))
+}
+
+/// Lists all tokens of a user, including expired ones
+pub async fn list_tokens(username: &str, pool: &PgPool) -> Result<Vec<ApiToken>> {
+    sqlx::query_as!(
+        ApiToken,
+        r#"
+        SELECT
+            id,
+            username,
+            name,
+            scopes,
+            expires_at,
+            created_at,
+            last_used_at
+        FROM api_token
+        WHERE username=$1
+        ORDER BY created_at
+        "#,
+        username,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Deletes a token of a user and returns whether it existed
+pub async fn delete_token(id: i32, username: &str, pool: &PgPool) -> Result<bool> {
+    let deleted = sqlx::query!(
+        r#"
+        DELETE FROM
+        api_token
+        WHERE id=$1
+        AND username=$2
+        "#,
+        id,
+        username,
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+    Ok(deleted > 0)
+}
@@ -1,16 +1,177 @@
-use serde::{Deserialize, Serialize};
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::{PgExecutor, PgPool};
+
+use super::tag;
+
+/// How visitors of a short URL are sent to its target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "redirect_type", rename_all = "snake_case")]
+pub enum RedirectType {
+    /// `301 Moved Permanently`, search engines attribute the short URL's ranking to the target
+    MovedPermanently,
+    /// `302 Found`, for example for campaign links
+    Found,
+    /// `307 Temporary Redirect`
+    TemporaryRedirect,
+    /// `308 Permanent Redirect`, like `301` but clients keep the request method
+    PermanentRedirect,
+    /// An HTML page which refreshes to the target
+    MetaRefresh,
+    /// An HTML page which shows the target in a frame, keeping the short URL in the address bar
+    Frame,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Url {
+    /// The domain the short URL is served on, empty for hosts without a configured domain
+    pub domain: String,
+    pub short: String,
+    pub target_url: String,
+    /// After this point in time, the short URL no longer redirects to its target
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Where visitors of an expired or used up short URL are sent instead
+    pub fallback_url: Option<String>,
+    /// How many more times the short URL may be visited
+    pub max_visits: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// The user who created the short URL, unknown for short URLs created before this was recorded
+    pub created_by: Option<String>,
+    /// A human readable name of the short URL
+    pub title: Option<String>,
+    /// Why the short URL exists
+    pub description: Option<String>,
+    pub tags: Vec<String>,
+    /// Why the short URL does not redirect anymore, is set while its target domain is blocked
+    pub disabled_reason: Option<String>,
+    /// If omitted, the server-wide default is used
+    pub redirect_type: Option<RedirectType>,
+    /// Whether the path following the short id is appended to the target
+    pub forward_path: bool,
+    /// Whether the query string of visits is merged into the one of the target
+    pub forward_query: bool,
+}
+
+/// The URLs a short URL sends its visitors to
+pub struct Targets {
+    pub domain: String,
     pub short: String,
     pub target_url: String,
+    pub fallback_url: Option<String>,
+    pub disabled_reason: Option<String>,
+}
+
+/// Describes a short URL which is about to be created
+#[derive(Debug, Deserialize)]
+pub struct NewUrl {
+    /// If omitted, the short URL is served on all hosts without a configured domain
+    #[serde(default)]
+    pub domain: String,
+    /// If omitted, the server generates a short id
+    pub short: Option<String>,
+    pub target_url: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub fallback_url: Option<String>,
+    pub max_visits: Option<i32>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub redirect_type: Option<RedirectType>,
+    #[serde(default)]
+    pub forward_path: bool,
+    #[serde(default)]
+    pub forward_query: bool,
+}
+
+/// Restricts which short URLs are listed
+pub struct UrlFilter<'filter> {
+    /// Only short URLs of this domain are listed
+    pub domain: &'filter str,
+    /// Only short ids starting with this prefix are listed
+    pub prefix: Option<&'filter str>,
+    /// Only short URLs whose target contains this text (ignoring case) are listed
+    pub search: Option<&'filter str>,
+    /// Only short URLs carrying this tag are listed
+    pub tag: Option<&'filter str>,
+}
+
+impl UrlFilter<'_> {
+    fn prefix_pattern(&self) -> Option<String> {
+        self.prefix
+            .map(|prefix| format!("{}%", escape_like(prefix)))
+    }
+
+    fn search_pattern(&self) -> Option<String> {
+        self.search
+            .map(|search| format!("%{}%", escape_like(search)))
+    }
+}
+
+/// Escapes the wildcard characters of a `LIKE` pattern
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Describes changes to an existing short URL, omitted fields are left unchanged
+/// Optional fields are removed by setting them to `null`
+#[derive(Debug, Deserialize)]
+pub struct UrlUpdate {
+    /// Renames the short URL
+    pub short: Option<String>,
+    pub target_url: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    pub expires_at: Option<Option<DateTime<Utc>>>,
+    #[serde(default, deserialize_with = "present")]
+    pub fallback_url: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub max_visits: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub title: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub description: Option<Option<String>>,
+    /// Replaces all tags of the short URL
+    pub tags: Option<Vec<String>>,
+    /// Attached besides the current tags, so that concurrent changes do not overwrite each other
+    pub add_tags: Option<Vec<String>>,
+    /// Detached while leaving all other tags untouched
+    pub remove_tags: Option<Vec<String>>,
+    pub redirect_type: Option<RedirectType>,
+    pub forward_path: Option<bool>,
+    pub forward_query: Option<bool>,
+}
+
+/// Tells fields which are `null` apart from omitted ones, which are `None` by default
+fn present<'de, T, D>(deserializer: D) -> std::result::Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::deserialize(deserializer).map(Some)
+}
+
+impl Url {
+    pub fn is_expired(&self) -> bool {
+        matches!(self.expires_at, Some(expires_at) if expires_at <= Utc::now())
+    }
+}
+
+impl NewUrl {
+    pub fn is_expired(&self) -> bool {
+        matches!(self.expires_at, Some(expires_at) if expires_at <= Utc::now())
+    }
 }
 
 #[derive(Debug)]
 pub enum Error {
     ShortExists,
     ShortDoesNotExist,
+    /// Adding tags would exceed `tag::MAX_TAGS`
+    TooManyTags,
     Database(sqlx::Error),
 }
 
@@ -22,36 +183,151 @@ impl From<sqlx::Error> for Error {
 
 pub type Result<T> = std::result::Result<T, Error>;
 
-pub async fn create_url(url: &Url, pool: &PgPool) -> Result<()> {
-    match sqlx::query!(
+/// The Postgres error code of `unique_violation`
+const UNIQUE_VIOLATION: &str = "23505";
+
+/// Creates a new short URL using the specified short id
+pub async fn create_url(short: &str, url: &NewUrl, created_by: &str, pool: &PgPool) -> Result<Url> {
+    let mut transaction = pool.begin().await?;
+
+    let created = sqlx::query!(
         r#"
         INSERT INTO
         url(
+            domain,
             short,
-            target_url
+            target_url,
+            expires_at,
+            fallback_url,
+            max_visits,
+            created_by,
+            title,
+            description,
+            redirect_type,
+            forward_path,
+            forward_query
         )
-        VALUES($1, $2)
-        ON CONFLICT (short) DO NOTHING
+        VALUES($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        ON CONFLICT (domain, short) DO NOTHING
         "#,
-        url.short,
+        url.domain,
+        short,
         url.target_url,
+        url.expires_at,
+        url.fallback_url,
+        url.max_visits,
+        created_by,
+        url.title,
+        url.description,
+        url.redirect_type as Option<RedirectType>,
+        url.forward_path,
+        url.forward_query,
     )
-    .execute(pool)
+    .execute(&mut transaction)
     .await?
-    .rows_affected()
-    {
-        0 => Err(Error::ShortExists),
-        _ => Ok(()),
+    .rows_affected();
+    if created == 0 {
+        return Err(Error::ShortExists);
+    }
+
+    tag::set_tags(&url.domain, short, &url.tags, &mut transaction).await?;
+    let url = get_url(&url.domain, short, &mut transaction).await?;
+    transaction.commit().await?;
+    Ok(url)
+}
+
+/// Changing the target or fallback decides again whether the short URL is disabled, the new
+/// reason is given by `disabled_reason`
+pub async fn update_url(
+    domain: &str,
+    short: &str,
+    update: &UrlUpdate,
+    disabled_reason: Option<Option<&str>>,
+    pool: &PgPool,
+) -> Result<Url> {
+    let mut transaction = pool.begin().await?;
+
+    // Renaming keeps the recorded visits as they reference the short id using `ON UPDATE CASCADE`
+    // Optional columns are only changed if their flag is set, so that they can be cleared
+    let updated = sqlx::query_scalar!(
+        r#"
+        UPDATE url
+        SET
+            short=COALESCE($3, short),
+            target_url=COALESCE($4, target_url),
+            expires_at=CASE WHEN $5 THEN $6 ELSE expires_at END,
+            fallback_url=CASE WHEN $7 THEN $8 ELSE fallback_url END,
+            max_visits=CASE WHEN $9 THEN $10 ELSE max_visits END,
+            title=CASE WHEN $11 THEN $12 ELSE title END,
+            description=CASE WHEN $13 THEN $14 ELSE description END,
+            redirect_type=COALESCE($15, redirect_type),
+            forward_path=COALESCE($16, forward_path),
+            forward_query=COALESCE($17, forward_query),
+            disabled_reason=CASE WHEN $18 THEN $19 ELSE disabled_reason END,
+            updated_at=now()
+        WHERE domain=$1
+        AND short=$2
+        RETURNING short
+        "#,
+        domain,
+        short,
+        update.short,
+        update.target_url,
+        update.expires_at.is_some(),
+        update.expires_at.flatten(),
+        update.fallback_url.is_some(),
+        update.fallback_url.clone().flatten(),
+        update.max_visits.is_some(),
+        update.max_visits.flatten(),
+        update.title.is_some(),
+        update.title.clone().flatten(),
+        update.description.is_some(),
+        update.description.clone().flatten(),
+        update.redirect_type as Option<RedirectType>,
+        update.forward_path,
+        update.forward_query,
+        disabled_reason.is_some(),
+        disabled_reason.flatten(),
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .map_err(|err| match &err {
+        sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some(UNIQUE_VIOLATION) => {
+            Error::ShortExists
+        }
+        _ => Error::Database(err),
+    })?;
+    let Some(short) = updated else {
+        return Err(Error::ShortDoesNotExist);
+    };
+
+    // The update above locks the short URL, so concurrent tag changes are applied one after another
+    if let Some(tags) = &update.tags {
+        tag::set_tags(domain, &short, tags, &mut transaction).await?;
+    }
+    if let Some(tags) = &update.add_tags {
+        tag::add_tags(domain, &short, tags, &mut transaction).await?;
+    }
+    if let Some(tags) = &update.remove_tags {
+        tag::remove_tags(domain, &short, tags, &mut transaction).await?;
     }
+    let url = get_url(domain, &short, &mut transaction).await?;
+    if url.tags.len() > tag::MAX_TAGS {
+        return Err(Error::TooManyTags);
+    }
+    transaction.commit().await?;
+    Ok(url)
 }
 
-pub async fn delete_url(short: &str, pool: &PgPool) -> Result<()> {
+pub async fn delete_url(domain: &str, short: &str, pool: &PgPool) -> Result<()> {
     match sqlx::query!(
         r#"
         DELETE FROM
         url
-        WHERE short=$1
+        WHERE domain=$1
+        AND short=$2
         "#,
+        domain,
         short,
     )
     .execute(pool)
@@ -63,19 +339,44 @@ pub async fn delete_url(short: &str, pool: &PgPool) -> Result<()> {
     }
 }
 
-pub async fn get_url(short: &str, pool: &PgPool) -> Result<Url> {
+pub async fn get_url<'executor>(
+    domain: &str,
+    short: &str,
+    executor: impl PgExecutor<'executor>,
+) -> Result<Url> {
     let url = sqlx::query_as!(
         Url,
         r#"
         SELECT
+            domain,
             short,
-            target_url
+            target_url,
+            expires_at,
+            fallback_url,
+            max_visits,
+            created_at,
+            updated_at,
+            created_by,
+            title,
+            description,
+            ARRAY(
+                SELECT tag FROM url_tag
+                WHERE url_tag.domain=url.domain
+                AND url_tag.short=url.short
+                ORDER BY tag
+            ) AS "tags!",
+            disabled_reason,
+            redirect_type AS "redirect_type: RedirectType",
+            forward_path,
+            forward_query
         FROM url
-        WHERE short=$1
+        WHERE domain=$1
+        AND short=$2
         "#,
+        domain,
         short,
     )
-    .fetch_optional(pool)
+    .fetch_optional(executor)
     .await?;
 
     match url {
@@ -84,18 +385,343 @@ pub async fn get_url(short: &str, pool: &PgPool) -> Result<Url> {
     }
 }
 
-pub async fn list_urls(pool: &PgPool, max_entries: i64) -> Result<Vec<Url>> {
+/// Lists short URLs ordered by their short id, starting after the specified short id
+pub async fn list_urls_by_short(
+    pool: &PgPool,
+    filter: &UrlFilter<'_>,
+    after: Option<&str>,
+    max_entries: i64,
+) -> Result<Vec<Url>> {
+    Ok(sqlx::query_as!(
+        Url,
+        r#"
+        SELECT
+            domain,
+            short,
+            target_url,
+            expires_at,
+            fallback_url,
+            max_visits,
+            created_at,
+            updated_at,
+            created_by,
+            title,
+            description,
+            ARRAY(
+                SELECT tag FROM url_tag
+                WHERE url_tag.domain=url.domain
+                AND url_tag.short=url.short
+                ORDER BY tag
+            ) AS "tags!",
+            disabled_reason,
+            redirect_type AS "redirect_type: RedirectType",
+            forward_path,
+            forward_query
+        FROM url
+        WHERE domain=$6
+        AND ($1::TEXT IS NULL OR short LIKE $1)
+        AND ($2::TEXT IS NULL OR target_url ILIKE $2)
+        AND ($3::TEXT IS NULL OR short > $3)
+        AND ($5::TEXT IS NULL OR EXISTS (
+            SELECT 1 FROM url_tag
+            WHERE url_tag.domain=url.domain
+            AND url_tag.short=url.short
+            AND url_tag.tag=$5
+        ))
+        ORDER BY short
+        LIMIT $4
+        "#,
+        filter.prefix_pattern(),
+        filter.search_pattern(),
+        after,
+        max_entries,
+        filter.tag,
+        filter.domain,
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Lists short URLs ordered by their creation time, starting after the specified short URL
+/// The short id breaks ties between short URLs which were created at the same time
+pub async fn list_urls_by_creation(
+    pool: &PgPool,
+    filter: &UrlFilter<'_>,
+    after: Option<(DateTime<Utc>, &str)>,
+    max_entries: i64,
+) -> Result<Vec<Url>> {
+    let (after_created_at, after_short) = after.unzip();
     Ok(sqlx::query_as!(
         Url,
         r#"
         SELECT
+            domain,
             short,
-            target_url
+            target_url,
+            expires_at,
+            fallback_url,
+            max_visits,
+            created_at,
+            updated_at,
+            created_by,
+            title,
+            description,
+            ARRAY(
+                SELECT tag FROM url_tag
+                WHERE url_tag.domain=url.domain
+                AND url_tag.short=url.short
+                ORDER BY tag
+            ) AS "tags!",
+            disabled_reason,
+            redirect_type AS "redirect_type: RedirectType",
+            forward_path,
+            forward_query
+        FROM url
+        WHERE domain=$7
+        AND ($1::TEXT IS NULL OR short LIKE $1)
+        AND ($2::TEXT IS NULL OR target_url ILIKE $2)
+        AND ($3::TIMESTAMPTZ IS NULL OR (created_at, short) > ($3, $4))
+        AND ($6::TEXT IS NULL OR EXISTS (
+            SELECT 1 FROM url_tag
+            WHERE url_tag.domain=url.domain
+            AND url_tag.short=url.short
+            AND url_tag.tag=$6
+        ))
+        ORDER BY created_at, short
+        LIMIT $5
+        "#,
+        filter.prefix_pattern(),
+        filter.search_pattern(),
+        after_created_at,
+        after_short,
+        max_entries,
+        filter.tag,
+        filter.domain,
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Counts all short URLs matching the filter
+pub async fn count_urls(pool: &PgPool, filter: &UrlFilter<'_>) -> Result<i64> {
+    Ok(sqlx::query_scalar!(
+        r#"
+        SELECT
+            COUNT(*) AS "count!"
+        FROM url
+        WHERE domain=$4
+        AND ($1::TEXT IS NULL OR short LIKE $1)
+        AND ($2::TEXT IS NULL OR target_url ILIKE $2)
+        AND ($3::TEXT IS NULL OR EXISTS (
+            SELECT 1 FROM url_tag
+            WHERE url_tag.domain=url.domain
+            AND url_tag.short=url.short
+            AND url_tag.tag=$3
+        ))
+        "#,
+        filter.prefix_pattern(),
+        filter.search_pattern(),
+        filter.tag,
+        filter.domain,
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
+/// Counts the short ids which would change if they were lowercased
+pub async fn count_case_sensitive_shorts(pool: &PgPool) -> Result<i64> {
+    Ok(sqlx::query_scalar!(
+        r#"
+        SELECT
+            COUNT(*) AS "count!"
         FROM url
-        LIMIT $1
+        WHERE short <> LOWER(short)
         "#,
+    )
+    .fetch_one(pool)
+    .await?)
+}
+
+/// Lists short ids of a domain which resemble the given one, the most similar first
+/// Short ids starting with the given one are included, so that incomplete ones are found as well
+pub async fn list_similar_shorts(
+    domain: &str,
+    short: &str,
+    max_entries: i64,
+    pool: &PgPool,
+) -> Result<Vec<String>> {
+    Ok(sqlx::query_scalar!(
+        r#"
+        SELECT
+            short
+        FROM url
+        WHERE domain=$1
+        AND disabled_reason IS NULL
+        AND (short % $2 OR short LIKE $3)
+        ORDER BY similarity(short, $2) DESC, short
+        LIMIT $4
+        "#,
+        domain,
+        short,
+        format!("{}%", escape_like(short)),
         max_entries,
     )
     .fetch_all(pool)
     .await?)
 }
+
+/// Lists the target and fallback URLs of all short URLs
+pub async fn list_targets(pool: &PgPool) -> Result<Vec<Targets>> {
+    Ok(sqlx::query_as!(
+        Targets,
+        r#"
+        SELECT
+            domain,
+            short,
+            target_url,
+            fallback_url,
+            disabled_reason
+        FROM url
+        "#,
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Disables a short URL for the specified reason or enables it again
+pub async fn set_disabled_reason(
+    domain: &str,
+    short: &str,
+    reason: Option<&str>,
+    pool: &PgPool,
+) -> Result<()> {
+    sqlx::query!(
+        r#"
+        UPDATE url
+        SET disabled_reason=$3
+        WHERE domain=$1
+        AND short=$2
+        "#,
+        domain,
+        short,
+        reason,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Atomically uses up one of the remaining visits of a visit-limited short URL
+/// Returns `false` if no visits are left
+pub async fn consume_visit(domain: &str, short: &str, pool: &PgPool) -> Result<bool> {
+    Ok(sqlx::query!(
+        r#"
+        UPDATE url
+        SET max_visits=max_visits - 1
+        WHERE domain=$1
+        AND short=$2
+        AND max_visits > 0
+        "#,
+        domain,
+        short,
+    )
+    .execute(pool)
+    .await?
+    .rows_affected()
+        > 0)
+}
+
+/// Deletes all short URLs which have been expired for longer than the grace period
+/// If `archive` is set, the deleted short URLs are moved into the archive table beforehand along
+/// with their tags
+/// Returns the number of swept short URLs
+pub async fn sweep_expired(pool: &PgPool, grace_period_secs: f64, archive: bool) -> Result<u64> {
+    let result = match archive {
+        true => {
+            sqlx::query!(
+                r#"
+            WITH expired AS (
+                DELETE FROM
+                url
+                WHERE expires_at < now() - make_interval(secs => $1)
+                RETURNING
+                    domain,
+                    short,
+                    target_url,
+                    expires_at,
+                    fallback_url,
+                    max_visits,
+                    created_at,
+                    updated_at,
+                    created_by,
+                    title,
+                    description,
+                    ARRAY(
+                        SELECT tag FROM url_tag
+                        WHERE url_tag.domain=url.domain
+                        AND url_tag.short=url.short
+                        ORDER BY tag
+                    ) AS tags,
+                    disabled_reason,
+                    redirect_type,
+                    forward_path,
+                    forward_query
+            )
+            INSERT INTO
+            url_archive(
+                domain,
+                short,
+                target_url,
+                expires_at,
+                fallback_url,
+                max_visits,
+                created_at,
+                updated_at,
+                created_by,
+                title,
+                description,
+                tags,
+                disabled_reason,
+                redirect_type,
+                forward_path,
+                forward_query
+            )
+            SELECT
+                domain,
+                short,
+                target_url,
+                expires_at,
+                fallback_url,
+                max_visits,
+                created_at,
+                updated_at,
+                created_by,
+                title,
+                description,
+                tags,
+                disabled_reason,
+                redirect_type,
+                forward_path,
+                forward_query
+            FROM expired
+            "#,
+                grace_period_secs,
+            )
+            .execute(pool)
+            .await?
+        }
+        false => {
+            sqlx::query!(
+                r#"
+            DELETE FROM
+            url
+            WHERE expires_at < now() - make_interval(secs => $1)
+            "#,
+                grace_period_secs,
+            )
+            .execute(pool)
+            .await?
+        }
+    };
+    Ok(result.rows_affected())
+}
@@ -0,0 +1,212 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+
+/// Determines which permissions a user has
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "user_role", rename_all = "lowercase")]
+pub enum Role {
+    /// May list and get short URLs
+    Viewer,
+    /// May additionally create short URLs and manage their own ones
+    Editor,
+    /// May do everything, including managing users and the short URLs of others
+    Admin,
+}
+
+impl Role {
+    pub fn permissions(&self) -> &'static [Permission] {
+        match self {
+            Role::Viewer => &[Permission::ViewUrls],
+            Role::Editor => &[Permission::ViewUrls, Permission::EditUrls],
+            Role::Admin => &[
+                Permission::ViewUrls,
+                Permission::EditUrls,
+                Permission::ManageAllUrls,
+                Permission::ManageUsers,
+            ],
+        }
+    }
+}
+
+/// What a route requires the authenticated user to be allowed to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Listing and getting short URLs, their statistics and tags
+    ViewUrls,
+    /// Creating short URLs and modifying or deleting one's own short URLs
+    EditUrls,
+    /// Modifying and deleting the short URLs of other users
+    ManageAllUrls,
+    /// Creating, modifying and deleting users
+    ManageUsers,
+}
+
+impl Permission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::ViewUrls => "view_urls",
+            Permission::EditUrls => "edit_urls",
+            Permission::ManageAllUrls => "manage_all_urls",
+            Permission::ManageUsers => "manage_users",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct User {
+    pub username: String,
+    #[serde(skip_serializing)]
+    pub password_hash: String,
+    pub role: Role,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    UserExists,
+    UserDoesNotExist,
+    Database(sqlx::Error),
+}
+
+impl From<sqlx::Error> for Error {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Database(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Creates the configured user as an admin or resets its password and role to the configured ones
+pub async fn bootstrap_admin(username: &str, password_hash: &str, pool: &PgPool) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO
+        users(
+            username,
+            password_hash,
+            role
+        )
+        VALUES($1, $2, 'admin')
+        ON CONFLICT (username) DO UPDATE
+        SET
+            password_hash=EXCLUDED.password_hash,
+            role='admin'
+        "#,
+        username,
+        password_hash,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn create_user(
+    username: &str,
+    password_hash: &str,
+    role: Role,
+    pool: &PgPool,
+) -> Result<User> {
+    sqlx::query_as!(
+        User,
+        r#"
+        INSERT INTO
+        users(
+            username,
+            password_hash,
+            role
+        )
+        VALUES($1, $2, $3)
+        ON CONFLICT (username) DO NOTHING
+        RETURNING username, password_hash, role AS "role: Role", created_at
+        "#,
+        username,
+        password_hash,
+        role as Role,
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(Error::UserExists)
+}
+
+pub async fn get_user(username: &str, pool: &PgPool) -> Result<User> {
+    sqlx::query_as!(
+        User,
+        r#"
+        SELECT
+            username,
+            password_hash,
+            role AS "role: Role",
+            created_at
+        FROM users
+        WHERE username=$1
+        "#,
+        username,
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(Error::UserDoesNotExist)
+}
+
+pub async fn list_users(pool: &PgPool) -> std::result::Result<Vec<User>, sqlx::Error> {
+    sqlx::query_as!(
+        User,
+        r#"
+        SELECT
+            username,
+            password_hash,
+            role AS "role: Role",
+            created_at
+        FROM users
+        ORDER BY username
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Changes the password or the role of a user, omitted values are left unchanged
+pub async fn update_user(
+    username: &str,
+    password_hash: Option<&str>,
+    role: Option<Role>,
+    pool: &PgPool,
+) -> Result<User> {
+    sqlx::query_as!(
+        User,
+        r#"
+        UPDATE users
+        SET
+            password_hash=COALESCE($2, password_hash),
+            role=COALESCE($3, role)
+        WHERE username=$1
+        RETURNING username, password_hash, role AS "role: Role", created_at
+        "#,
+        username,
+        password_hash,
+        role as Option<Role>,
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or(Error::UserDoesNotExist)
+}
+
+/// Deletes a user, the short URLs created by the user are kept
+pub async fn delete_user(username: &str, pool: &PgPool) -> Result<()> {
+    match sqlx::query!(
+        r#"
+        DELETE FROM
+        users
+        WHERE username=$1
+        "#,
+        username,
+    )
+    .execute(pool)
+    .await?
+    .rows_affected()
+    {
+        0 => Err(Error::UserDoesNotExist),
+        _ => Ok(()),
+    }
+}
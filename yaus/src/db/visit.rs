@@ -0,0 +1,91 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+#[derive(Debug, Serialize)]
+pub struct Visit {
+    pub visited_at: DateTime<Utc>,
+    pub referrer: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+/// Describes a visit which is about to be recorded
+pub struct NewVisit<'visit> {
+    pub domain: &'visit str,
+    pub short: &'visit str,
+    pub referrer: Option<&'visit str>,
+    pub user_agent: Option<&'visit str>,
+    pub ip: Option<&'visit str>,
+}
+
+pub type Result<T> = std::result::Result<T, sqlx::Error>;
+
+pub async fn record_visit(visit: &NewVisit<'_>, pool: &PgPool) -> Result<()> {
+    sqlx::query!(
+        r#"
+        INSERT INTO
+        visit(
+            domain,
+            short,
+            referrer,
+            user_agent,
+            ip
+        )
+        VALUES($1, $2, $3, $4, $5)
+        "#,
+        visit.domain,
+        visit.short,
+        visit.referrer,
+        visit.user_agent,
+        visit.ip,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+pub async fn count_visits(domain: &str, short: &str, pool: &PgPool) -> Result<i64> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT
+            COUNT(*) AS "count!"
+        FROM visit
+        WHERE domain=$1
+        AND short=$2
+        "#,
+        domain,
+        short,
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Returns the most recent visits of a short URL, newest first
+pub async fn list_visits(
+    domain: &str,
+    short: &str,
+    pool: &PgPool,
+    max_entries: i64,
+) -> Result<Vec<Visit>> {
+    sqlx::query_as!(
+        Visit,
+        r#"
+        SELECT
+            visited_at,
+            referrer,
+            user_agent,
+            ip
+        FROM visit
+        WHERE domain=$1
+        AND short=$2
+        ORDER BY visited_at DESC, id DESC
+        LIMIT $3
+        "#,
+        domain,
+        short,
+        max_entries,
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,267 @@
+use std::{fs, io, sync::RwLock, time::SystemTime};
+
+use crate::config::TargetConfig;
+
+/// A domain like `example.com` or a wildcard like `*.example.com` which matches all subdomains
+#[derive(Debug, Clone, PartialEq)]
+enum Pattern {
+    Exact(String),
+    Subdomains(String),
+    Any,
+}
+
+impl Pattern {
+    /// Internationalized domains are converted to punycode in order to match normalized hosts
+    fn parse(pattern: &str) -> Option<Self> {
+        let pattern = pattern.trim().trim_end_matches('.').to_lowercase();
+        let (wildcard, domain) = match pattern.as_str() {
+            "*" => return Some(Pattern::Any),
+            _ => match pattern.strip_prefix("*.") {
+                Some(domain) => (true, domain),
+                None => (false, pattern.as_str()),
+            },
+        };
+        let domain = match url::Host::parse(domain) {
+            Ok(host) => host.to_string(),
+            Err(err) => {
+                warn!("Ignoring invalid domain pattern `{pattern}`: {err}");
+                return None;
+            }
+        };
+        Some(match wildcard {
+            true => Pattern::Subdomains(domain),
+            false => Pattern::Exact(domain),
+        })
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        match self {
+            Pattern::Exact(domain) => host == domain,
+            Pattern::Subdomains(domain) => host
+                .strip_suffix(domain.as_str())
+                .is_some_and(|subdomain| subdomain.ends_with('.')),
+            Pattern::Any => true,
+        }
+    }
+}
+
+/// A list of patterns from the configuration and optionally from a file
+#[derive(Default)]
+struct PatternList {
+    configured: Vec<Pattern>,
+    loaded: Vec<Pattern>,
+    /// When the file was modified before it was loaded the last time
+    modified: Option<SystemTime>,
+}
+
+impl PatternList {
+    fn new(configured: &[String]) -> Self {
+        Self {
+            configured: Self::parse(configured),
+            ..Default::default()
+        }
+    }
+
+    fn parse(lines: impl IntoIterator<Item = impl AsRef<str>>) -> Vec<Pattern> {
+        lines
+            .into_iter()
+            .filter_map(|line| {
+                let line = line.as_ref();
+                let pattern = line.split('#').next().unwrap_or(line).trim();
+                (!pattern.is_empty()).then(|| Pattern::parse(pattern))?
+            })
+            .collect()
+    }
+
+    /// Loads the patterns of the file again if it has changed since it was loaded
+    /// Returns whether the patterns were reloaded
+    fn reload(&mut self, file: &str) -> io::Result<bool> {
+        let modified = fs::metadata(file)?.modified()?;
+        if self.modified == Some(modified) {
+            return Ok(false);
+        }
+        self.loaded = Self::parse(fs::read_to_string(file)?.lines());
+        self.modified = Some(modified);
+        Ok(true)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.configured.is_empty() && self.loaded.is_empty()
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        self.configured
+            .iter()
+            .chain(&self.loaded)
+            .any(|pattern| pattern.matches(host))
+    }
+}
+
+/// Decides which domains short URLs may point at, using a block-list and an optional allow-list
+pub struct DomainFilter {
+    blocked_file: Option<String>,
+    allowed_file: Option<String>,
+    blocked: RwLock<PatternList>,
+    allowed: RwLock<PatternList>,
+}
+
+impl DomainFilter {
+    pub fn new(config: &TargetConfig) -> Self {
+        let filter = Self {
+            blocked_file: config.blocked_domains_file.clone(),
+            allowed_file: config.allowed_domains_file.clone(),
+            blocked: RwLock::new(PatternList::new(&config.blocked_domains)),
+            allowed: RwLock::new(PatternList::new(&config.allowed_domains)),
+        };
+        filter.reload();
+        filter
+    }
+
+    /// Loads the configured files again if they have changed
+    /// Returns whether any list was reloaded, unreadable files keep their previous patterns
+    pub fn reload(&self) -> bool {
+        let mut reloaded = false;
+        for (name, list, file) in [
+            ("blocked", &self.blocked, &self.blocked_file),
+            ("allowed", &self.allowed, &self.allowed_file),
+        ] {
+            let Some(file) = file else {
+                continue;
+            };
+            let mut list = list.write().expect("Domain filter lock is poisoned");
+            match list.reload(file) {
+                Ok(true) => {
+                    info!(
+                        "Loaded {} {name} domain pattern(s) from `{file}`",
+                        list.loaded.len()
+                    );
+                    reloaded = true;
+                }
+                Ok(false) => {}
+                Err(err) => error!("Could not load {name} domains from `{file}`: {err}"),
+            }
+        }
+        reloaded
+    }
+
+    /// Returns why a short URL may not point at the host, if it may not
+    pub fn check(&self, host: &str) -> Result<(), String> {
+        let host = host.trim_end_matches('.').to_lowercase();
+        if self
+            .blocked
+            .read()
+            .expect("Domain filter lock is poisoned")
+            .matches(&host)
+        {
+            return Err(format!("the domain `{host}` is blocked"));
+        }
+        let allowed = self.allowed.read().expect("Domain filter lock is poisoned");
+        if !allowed.is_empty() && !allowed.matches(&host) {
+            return Err(format!("the domain `{host}` is not on the allow-list"));
+        }
+        Ok(())
+    }
+
+    /// Checks the host of a stored target URL, URLs without a host are always accepted
+    pub fn check_url(&self, url: &str) -> Result<(), String> {
+        match url::Url::parse(url) {
+            Ok(url) => url.host_str().map_or(Ok(()), |host| self.check(host)),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(blocked: &[&str], allowed: &[&str]) -> DomainFilter {
+        DomainFilter::new(&TargetConfig {
+            blocked_domains: blocked.iter().map(|domain| domain.to_string()).collect(),
+            allowed_domains: allowed.iter().map(|domain| domain.to_string()).collect(),
+            ..TargetConfig::default()
+        })
+    }
+
+    #[test]
+    fn pattern_matches_exact_domains() {
+        let pattern = Pattern::parse("example.com").unwrap();
+        assert!(pattern.matches("example.com"));
+        assert!(!pattern.matches("www.example.com"));
+        assert!(!pattern.matches("badexample.com"));
+    }
+
+    #[test]
+    fn pattern_matches_subdomains() {
+        let pattern = Pattern::parse("*.example.com").unwrap();
+        assert!(pattern.matches("www.example.com"));
+        assert!(pattern.matches("a.b.example.com"));
+        assert!(!pattern.matches("example.com"));
+        assert!(!pattern.matches("badexample.com"));
+    }
+
+    #[test]
+    fn pattern_matches_anything() {
+        assert_eq!(Pattern::parse("*"), Some(Pattern::Any));
+        assert!(Pattern::Any.matches("example.com"));
+    }
+
+    #[test]
+    fn pattern_is_normalized() {
+        assert_eq!(
+            Pattern::parse(" Example.COM. "),
+            Some(Pattern::Exact("example.com".to_string()))
+        );
+        assert_eq!(
+            Pattern::parse("*.MÜNCHEN.de"),
+            Some(Pattern::Subdomains("xn--mnchen-3ya.de".to_string()))
+        );
+        assert_eq!(Pattern::parse("exa mple.com"), None);
+    }
+
+    #[test]
+    fn pattern_list_skips_comments_and_blank_lines() {
+        let patterns = PatternList::parse([
+            "# blocked domains",
+            "",
+            "   ",
+            "example.com # spam",
+            "*.example.org",
+            "exa mple.com",
+        ]);
+        assert_eq!(
+            patterns,
+            [
+                Pattern::Exact("example.com".to_string()),
+                Pattern::Subdomains("example.org".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn check_blocks_listed_domains() {
+        let filter = filter(&["example.com", "*.example.org"], &[]);
+        assert!(filter.check("example.com").is_err());
+        assert!(filter.check("EXAMPLE.com.").is_err());
+        assert!(filter.check("www.example.org").is_err());
+        assert!(filter.check("example.org").is_ok());
+        assert!(filter.check("example.net").is_ok());
+    }
+
+    #[test]
+    fn check_enforces_the_allow_list() {
+        let filter = filter(&["bad.example.com"], &["*.example.com"]);
+        assert!(filter.check("www.example.com").is_ok());
+        assert!(filter.check("bad.example.com").is_err());
+        assert!(filter.check("example.net").is_err());
+    }
+
+    #[test]
+    fn check_url_normalizes_internationalized_hosts() {
+        let filter = filter(&["münchen.de"], &[]);
+        assert!(filter.check_url("https://MÜNCHEN.de/").is_err());
+        assert!(filter.check_url("https://xn--mnchen-3ya.de./path").is_err());
+        assert!(filter.check_url("https://berlin.de/").is_ok());
+        assert!(filter.check_url("mailto:someone@münchen.de").is_ok());
+    }
+}
@@ -0,0 +1,109 @@
+use std::{sync::Arc, time::Duration};
+
+use actix_web::rt::{self, time};
+use sqlx::PgPool;
+
+use crate::config::{ExpiryAction, ExpiryConfig};
+use crate::db::{stats, url};
+use crate::domain_filter::DomainFilter;
+
+/// Periodically compacts recorded visits into hourly and daily rollups
+pub fn spawn_rollups(pool: PgPool, interval: Duration) {
+    rt::spawn(async move {
+        let mut interval = time::interval(interval);
+        loop {
+            interval.tick().await;
+            match stats::compact_visits(&pool).await {
+                Ok(0) => trace!("No new visits to compact"),
+                Ok(count) => debug!("Compacted {count} visit(s) into rollups"),
+                Err(err) => error!("Could not compact visits into rollups: {err}"),
+            }
+        }
+    });
+}
+
+/// Periodically deletes or archives short URLs whose grace period after expiry has passed
+pub fn spawn_expiry_sweeper(pool: PgPool, config: &ExpiryConfig) {
+    let grace_period_secs = config.grace_period as f64;
+    let archive = matches!(config.action, ExpiryAction::Archive);
+    let mut interval = time::interval(Duration::from_secs(config.sweep_interval.max(1)));
+    rt::spawn(async move {
+        loop {
+            interval.tick().await;
+            match url::sweep_expired(&pool, grace_period_secs, archive).await {
+                Ok(0) => trace!("No expired short URLs to sweep"),
+                Ok(count) => info!(
+                    "{} {count} expired short URL(s)",
+                    if archive { "Archived" } else { "Deleted" }
+                ),
+                Err(err) => error!("Could not sweep expired short URLs: {err:?}"),
+            }
+        }
+    });
+}
+
+/// Disables short URLs whose target or fallback domain is not allowed anymore
+/// and enables short URLs again once their domains are allowed
+/// Returns the number of disabled and enabled short URLs
+async fn rescan_targets(pool: &PgPool, filter: &DomainFilter) -> url::Result<(u64, u64)> {
+    let (mut disabled, mut enabled) = (0, 0);
+    for targets in url::list_targets(pool).await? {
+        let reason = filter
+            .check_url(&targets.target_url)
+            .and_then(|_| match &targets.fallback_url {
+                Some(fallback_url) => filter.check_url(fallback_url),
+                None => Ok(()),
+            })
+            .err();
+        if reason == targets.disabled_reason {
+            continue;
+        }
+        url::set_disabled_reason(&targets.domain, &targets.short, reason.as_deref(), pool).await?;
+        match reason {
+            Some(reason) => {
+                warn!(
+                    "Disabled short URL `{}/{}`: {reason}",
+                    targets.domain, targets.short
+                );
+                disabled += 1;
+            }
+            None => {
+                info!(
+                    "Enabled short URL `{}/{}` again",
+                    targets.domain, targets.short
+                );
+                enabled += 1;
+            }
+        }
+    }
+    Ok((disabled, enabled))
+}
+
+/// Periodically reloads the domain lists and checks all short URLs again once they have changed
+pub fn spawn_target_rescan(pool: PgPool, filter: Arc<DomainFilter>, interval: Duration) {
+    rt::spawn(async move {
+        let mut interval = time::interval(interval);
+        // The configured lists might have changed since the last start
+        let mut changed = true;
+        loop {
+            interval.tick().await;
+            changed |= filter.reload();
+            if !changed {
+                trace!("The domain lists have not changed");
+                continue;
+            }
+            match rescan_targets(&pool, &filter).await {
+                Ok((0, 0)) => debug!("All short URLs still point at allowed domains"),
+                Ok((disabled, enabled)) => info!(
+                    "Disabled {disabled} and enabled {enabled} short URL(s) after checking their domains"
+                ),
+                // The next tick tries again
+                Err(err) => {
+                    error!("Could not check the domains of short URLs: {err:?}");
+                    continue;
+                }
+            }
+            changed = false;
+        }
+    });
+}
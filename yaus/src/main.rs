@@ -1,12 +1,16 @@
-use std::{env, process};
+use std::{env, net::IpAddr, process, sync::Arc, time::Duration};
 
 use actix_web::{
+    http::header::HeaderValue,
     middleware::Logger,
     web::{self, Data},
     App, HttpResponse, HttpServer,
 };
-use api::ValidCredentials;
-use config::User;
+use api::{LockoutTracker, Pages, RateLimit, RateLimiter, RequirePermission, ValidCredentials};
+use config::{QueryConflict, ShortIdConfig, TargetConfig};
+use db::url::RedirectType;
+use db::user::Permission;
+use domain_filter::DomainFilter;
 use sqlx::PgPool;
 
 #[macro_use]
@@ -15,12 +19,28 @@ extern crate log;
 mod api;
 mod config;
 mod db;
+mod domain_filter;
+mod jobs;
+mod password;
+mod short_id;
 
 use config::Error as ConfigError;
 
 pub struct State {
     pub db_pool: PgPool,
-    pub user: User,
+    pub allow_query_credentials: bool,
+    pub trusted_proxies: Vec<IpAddr>,
+    pub default_redirect_type: RedirectType,
+    pub query_conflict: QueryConflict,
+    pub lockouts: Arc<LockoutTracker>,
+    pub short_id: ShortIdConfig,
+    /// The normalized names of all configured domains
+    pub domains: Vec<String>,
+    pub target: TargetConfig,
+    pub domain_filter: Arc<DomainFilter>,
+    /// Where visitors of `/` are redirected to
+    pub root_redirect: Option<String>,
+    pub pages: Arc<Pages>,
 }
 
 #[actix_web::main]
@@ -28,6 +48,11 @@ async fn main() {
     //std::env::set_var("RUST_LOG", "debug");
     env_logger::init();
 
+    // `yaus hash-password` prints a hash for the configuration instead of starting the server
+    if env::args().nth(1).as_deref() == Some("hash-password") {
+        process::exit(password::hash_password_command());
+    }
+
     let config_path = &env::var("YAUS_CONFIG_PATH").unwrap_or("config.toml".to_string());
     let mut conf = match config::read_config(config_path) {
         Ok(config) => config,
@@ -51,8 +76,7 @@ async fn main() {
         Err(err) => {
             error!(
                 "Could not initialize database connection: {}\n{:?}",
-                err.to_string(),
-                &conf.database,
+                err, &conf.database,
             );
             process::exit(1);
         }
@@ -61,34 +85,225 @@ async fn main() {
 
     // Run sqlx migrations on startup
     if let Err(err) = db::run_migrations(&db_pool).await {
-        error!(
-            "Could not run startup database migration: {}",
-            err.to_string()
-        );
+        error!("Could not run startup database migration: {}", err);
         process::exit(1);
     };
 
+    // The configured user is always available as an admin, even if all other users are lost
+    if conf.user.password_hash.is_some() && conf.user.password.is_some() {
+        warn!("Both `password` and `password_hash` are configured, `password` is ignored");
+    }
+    let password_hash = match (&conf.user.password_hash, &conf.user.password) {
+        (Some(hash), _) if password::is_valid_hash(hash) => hash.clone(),
+        (Some(_), _) => {
+            error!(
+                "The `password_hash` of the configured user is neither an argon2 nor a bcrypt hash"
+            );
+            process::exit(1);
+        }
+        (None, Some(password)) => match password::hash(password) {
+            Ok(hash) => hash,
+            Err(err) => {
+                error!("Could not hash the password of the configured user: {err}");
+                process::exit(1);
+            }
+        },
+        (None, None) => {
+            error!("The configured user requires either a `password_hash` or a `password`");
+            process::exit(1);
+        }
+    };
+    if let Err(err) = db::user::bootstrap_admin(&conf.user.username, &password_hash, &db_pool).await
+    {
+        error!("Could not create the configured user: {err:?}");
+        process::exit(1);
+    }
+
+    // Visitors cannot reach short ids containing uppercase letters if lookups ignore case
+    if conf.short_id.case_insensitive {
+        match db::url::count_case_sensitive_shorts(&db_pool).await {
+            Ok(0) => {}
+            Ok(count) => warn!(
+                "{count} short id(s) contain uppercase letters and cannot be visited while short ids are case-insensitive, rename them in lowercase"
+            ),
+            Err(err) => error!("Could not check short ids for uppercase letters: {err:?}"),
+        }
+    }
+
+    // Start background jobs
+    jobs::spawn_rollups(
+        db_pool.clone(),
+        Duration::from_secs(conf.stats.rollup_interval.max(1)),
+    );
+    jobs::spawn_expiry_sweeper(db_pool.clone(), &conf.expiry);
+    let domain_filter = Arc::new(DomainFilter::new(&conf.target));
+    jobs::spawn_target_rescan(
+        db_pool.clone(),
+        Arc::clone(&domain_filter),
+        Duration::from_secs(conf.target.rescan_interval.max(1)),
+    );
+
+    // The rate limiters are shared by all workers
+    for (name, limit) in [
+        ("redirect", &conf.rate_limit.redirect),
+        ("api", &conf.rate_limit.api),
+    ] {
+        if let Err(err) = limit.validate() {
+            error!("Invalid rate limit `rate_limit.{name}`: {err}");
+            process::exit(1);
+        }
+    }
+    let redirect_limiter = Arc::new(RateLimiter::new(conf.rate_limit.redirect));
+    let api_limiter = Arc::new(RateLimiter::new(conf.rate_limit.api));
+    let lockouts = Arc::new(LockoutTracker::new(conf.lockout));
+
+    // Each configured domain serves its own set of short URLs
+    let domains = conf
+        .domains
+        .iter()
+        .map(|domain| api::normalize_domain(&domain.name))
+        .filter(|domain| !domain.is_empty())
+        .collect::<Vec<String>>();
+    if !domains.is_empty() {
+        info!("Serving short URLs for the domains {}", domains.join(", "));
+    }
+
+    // The landing page and error templates are read once, so that broken paths fail on startup
+    let pages = match Pages::load(&conf.server) {
+        Ok(pages) => Arc::new(pages),
+        Err(err) => {
+            error!("{err}");
+            process::exit(1);
+        }
+    };
+    if let Some(root_redirect) = &conf.server.root_redirect {
+        if HeaderValue::from_str(root_redirect).is_err() {
+            error!("The `root_redirect` `{root_redirect}` is not a valid URL");
+            process::exit(1);
+        }
+    }
+
     // Create the server
     let server = match HttpServer::new(move || {
-        let logger = Logger::default();
+        // Like the default format, but without the passwords of the request line
+        let logger =
+            Logger::new(r#"%a "%{request_line}xi" %s %b "%{Referer}i" "%{User-Agent}i" %T"#)
+                .custom_request_replace("request_line", api::redacted_request_line);
 
         App::new()
             .wrap(logger)
             .app_data(Data::new(State {
                 db_pool: db_pool.clone(),
-                user: conf.user.clone(),
+                allow_query_credentials: conf.server.allow_query_credentials,
+                trusted_proxies: conf.server.trusted_proxies.clone(),
+                default_redirect_type: conf.server.default_redirect_type,
+                query_conflict: conf.passthrough.query_conflict,
+                lockouts: Arc::clone(&lockouts),
+                short_id: conf.short_id.clone(),
+                domains: domains.clone(),
+                target: conf.target.clone(),
+                domain_filter: Arc::clone(&domain_filter),
+                root_redirect: conf.server.root_redirect.clone(),
+                pages: Arc::clone(&pages),
             }))
-            .service(api::handle_redirect)
             .service(
                 // Is required in order to enable the authentication middleware just for the `/api` scope
                 web::scope("/api")
                     .wrap(ValidCredentials)
-                    .route("/auth", web::get().to(|| HttpResponse::Ok()))
-                    .route("/url/{short_id}", web::get().to(api::get_target))
-                    .route("/url", web::post().to(api::create_url))
-                    .route("/url/{short_id}", web::delete().to(api::delete_url))
-                    .route("/urls/{limit}", web::get().to(api::list_urls)),
+                    .wrap(RateLimit(Arc::clone(&api_limiter)))
+                    .route("/auth", web::get().to(HttpResponse::Ok))
+                    .route(
+                        "/url/{short_id}",
+                        web::get()
+                            .to(api::get_target)
+                            .wrap(RequirePermission(Permission::ViewUrls)),
+                    )
+                    .route(
+                        "/url",
+                        web::post()
+                            .to(api::create_url)
+                            .wrap(RequirePermission(Permission::EditUrls)),
+                    )
+                    .route(
+                        "/url/{short_id}",
+                        web::patch()
+                            .to(api::update_url)
+                            .wrap(RequirePermission(Permission::EditUrls)),
+                    )
+                    .route(
+                        "/url/{short_id}",
+                        web::delete()
+                            .to(api::delete_url)
+                            .wrap(RequirePermission(Permission::EditUrls)),
+                    )
+                    .route(
+                        "/url/{short_id}/stats",
+                        web::get()
+                            .to(api::get_stats)
+                            .wrap(RequirePermission(Permission::ViewUrls)),
+                    )
+                    .route(
+                        "/url/{short_id}/timeseries",
+                        web::get()
+                            .to(api::get_timeseries)
+                            .wrap(RequirePermission(Permission::ViewUrls)),
+                    )
+                    .route(
+                        "/urls",
+                        web::get()
+                            .to(api::list_urls)
+                            .wrap(RequirePermission(Permission::ViewUrls)),
+                    )
+                    .route(
+                        "/tags",
+                        web::get()
+                            .to(api::list_tags)
+                            .wrap(RequirePermission(Permission::ViewUrls)),
+                    )
+                    .route(
+                        "/users",
+                        web::get()
+                            .to(api::list_users)
+                            .wrap(RequirePermission(Permission::ManageUsers)),
+                    )
+                    .route(
+                        "/users",
+                        web::post()
+                            .to(api::create_user)
+                            .wrap(RequirePermission(Permission::ManageUsers)),
+                    )
+                    // Users may change their own password, so the permission is checked by the handler
+                    .route("/users/{username}", web::patch().to(api::update_user))
+                    .route(
+                        "/users/{username}",
+                        web::delete()
+                            .to(api::delete_user)
+                            .wrap(RequirePermission(Permission::ManageUsers)),
+                    )
+                    .route(
+                        "/lockouts",
+                        web::get()
+                            .to(api::list_lockouts)
+                            .wrap(RequirePermission(Permission::ManageUsers)),
+                    )
+                    .route(
+                        "/lockouts",
+                        web::delete()
+                            .to(api::clear_lockouts)
+                            .wrap(RequirePermission(Permission::ManageUsers)),
+                    )
+                    .route("/tokens", web::get().to(api::list_tokens))
+                    .route("/tokens", web::post().to(api::create_token))
+                    .route("/tokens/{id}", web::delete().to(api::delete_token)),
+            )
+            .service(
+                // Must be registered after the `/api` scope as the empty scope matches every path
+                web::scope("")
+                    .wrap(RateLimit(Arc::clone(&redirect_limiter)))
+                    .route("/", web::get().to(api::handle_root))
+                    .service(api::handle_redirect),
             )
+            .default_service(web::to(api::handle_not_found))
     })
     .bind(("::0", conf.server.port))
     {
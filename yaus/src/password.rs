@@ -0,0 +1,106 @@
+use std::{
+    io::{self, BufRead, IsTerminal},
+    sync::OnceLock,
+};
+
+use argon2::{
+    password_hash::{
+        rand_core::OsRng, Error, PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+    },
+    Argon2,
+};
+
+/// Hashes a password using argon2 with a random salt, the result is a PHC string
+pub fn hash(password: &str) -> Result<String, Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
+}
+
+/// bcrypt hashes use their own format instead of the PHC string format
+fn is_bcrypt(hash: &str) -> bool {
+    ["$2a$", "$2b$", "$2x$", "$2y$"]
+        .iter()
+        .any(|prefix| hash.starts_with(prefix))
+}
+
+/// Checks whether a hash is an argon2 PHC string or a bcrypt hash
+/// PHC strings of other algorithms, like `$scrypt$`, could never be verified
+pub fn is_valid_hash(hash: &str) -> bool {
+    match is_bcrypt(hash) {
+        true => hash.parse::<bcrypt::HashParts>().is_ok(),
+        false => PasswordHash::new(hash).is_ok_and(|hash| {
+            matches!(hash.algorithm.as_str(), "argon2id" | "argon2i" | "argon2d")
+        }),
+    }
+}
+
+/// Checks whether a password matches an argon2 or bcrypt hash
+/// Both compare the derived keys in constant time, malformed hashes never match
+pub fn verify(password: &str, hash: &str) -> bool {
+    if is_bcrypt(hash) {
+        return match bcrypt::verify(password, hash) {
+            Ok(matches) => matches,
+            Err(err) => {
+                error!("Stored bcrypt password hash is malformed: {err}");
+                false
+            }
+        };
+    }
+    match PasswordHash::new(hash) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(err) => {
+            error!("Stored password hash is malformed: {err}");
+            false
+        }
+    }
+}
+
+/// Verifies a password against a throwaway hash
+/// Is used for unknown usernames, so that the response time does not reveal which users exist
+pub fn verify_dummy(password: &str) {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    let dummy_hash =
+        DUMMY_HASH.get_or_init(|| hash("dummy").expect("Hashing a fixed password cannot fail"));
+    verify(password, dummy_hash);
+}
+
+/// Implements `yaus hash-password`: reads a password from stdin and prints its hash
+/// The hash can be used as the `password_hash` of the `[user]` section
+pub fn hash_password_command() -> i32 {
+    let stdin = io::stdin();
+    let password = match stdin.is_terminal() {
+        true => rpassword::read_password_from_tty(Some("Password: ")),
+        false => {
+            let mut line = String::new();
+            stdin
+                .lock()
+                .read_line(&mut line)
+                .map(|_| line.trim_end_matches(['\r', '\n']).to_string())
+        }
+    };
+    let password = match password {
+        Ok(password) if !password.is_empty() => password,
+        Ok(_) => {
+            eprintln!("The password may not be empty");
+            return 1;
+        }
+        Err(err) => {
+            eprintln!("Could not read password: {err}");
+            return 1;
+        }
+    };
+    match hash(&password) {
+        Ok(hash) => {
+            println!("{hash}");
+            0
+        }
+        Err(err) => {
+            eprintln!("Could not hash password: {err}");
+            1
+        }
+    }
+}
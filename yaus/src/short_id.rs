@@ -0,0 +1,236 @@
+use rand::{distributions::Alphanumeric, seq::SliceRandom, Rng};
+use sqlx::PgPool;
+use unicode_normalization::UnicodeNormalization;
+
+use crate::config::{ShortIdConfig, ShortIdStrategy};
+
+/// Short ids are stored as `VARCHAR(20)`
+const MAX_LENGTH: usize = 20;
+
+const BASE62_ALPHABET: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+const ADJECTIVES: &[&str] = &[
+    "able", "bold", "brave", "bright", "calm", "clever", "cool", "cosy", "crisp", "curly",
+    "daring", "eager", "early", "fair", "fancy", "fast", "fine", "fluffy", "fond", "free", "fresh",
+    "gentle", "glad", "golden", "grand", "happy", "hardy", "honest", "humble", "jolly", "keen",
+    "kind", "lively", "loyal", "lucky", "merry", "mighty", "misty", "neat", "nimble", "noble",
+    "plucky", "polite", "proud", "quick", "quiet", "rapid", "ready", "rosy", "shiny", "silent",
+    "sleek", "smart", "snappy", "sunny", "swift", "tidy", "tiny", "vivid", "warm", "wild", "wise",
+    "witty", "young",
+];
+
+const NOUNS: &[&str] = &[
+    "badger", "beaver", "bee", "bison", "cat", "cobra", "crane", "crow", "deer", "dingo", "dog",
+    "dove", "duck", "eagle", "eel", "falcon", "ferret", "finch", "fox", "frog", "gecko", "goat",
+    "goose", "hare", "hawk", "heron", "horse", "ibis", "koala", "lark", "lemur", "lion", "llama",
+    "lynx", "mole", "moose", "moth", "newt", "otter", "owl", "panda", "parrot", "pony", "puffin",
+    "quail", "rabbit", "raven", "robin", "seal", "shark", "sloth", "snail", "stork", "swan",
+    "tiger", "toad", "trout", "turtle", "viper", "walrus", "whale", "wolf", "wombat", "yak",
+];
+
+/// Encodes a number using the digits and the lower- and uppercase ASCII letters
+fn base62(mut value: u64) -> String {
+    let mut encoded = vec![];
+    loop {
+        encoded.push(BASE62_ALPHABET[(value % 62) as usize]);
+        value /= 62;
+        if value == 0 {
+            break;
+        }
+    }
+    encoded.reverse();
+    String::from_utf8(encoded).expect("The base62 alphabet only contains ASCII characters")
+}
+
+/// Generates a new short id using the configured strategy
+/// The generated short id might already be taken or be invalid, callers have to retry then
+pub async fn generate(config: &ShortIdConfig, pool: &PgPool) -> Result<String, sqlx::Error> {
+    Ok(match config.strategy {
+        ShortIdStrategy::Random => rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(config.length.clamp(1, MAX_LENGTH))
+            .map(char::from)
+            .collect(),
+        ShortIdStrategy::Sequential => {
+            let value = sqlx::query_scalar!(r#"SELECT nextval('short_id_seq') AS "value!""#)
+                .fetch_one(pool)
+                .await?;
+            base62(value as u64)
+        }
+        ShortIdStrategy::Words => {
+            let mut rng = rand::thread_rng();
+            format!(
+                "{}-{}",
+                ADJECTIVES
+                    .choose(&mut rng)
+                    .expect("Adjectives are not empty"),
+                NOUNS.choose(&mut rng).expect("Nouns are not empty"),
+            )
+        }
+    })
+}
+
+/// Applies Unicode NFC normalization and lowercases the short id if short ids are case-insensitive
+/// Is applied to requested short ids before they are looked up
+pub fn normalize(short: &str, config: &ShortIdConfig) -> String {
+    let short = short.nfc().collect::<String>();
+    match config.case_insensitive {
+        true => short.to_lowercase(),
+        false => short,
+    }
+}
+
+/// Checks whether a character is part of a set like `a-zA-Z0-9_-`
+fn is_allowed(character: char, allowed_characters: &str) -> bool {
+    let allowed = allowed_characters.chars().collect::<Vec<char>>();
+    let mut index = 0;
+    while index < allowed.len() {
+        // A `-` between two characters denotes a range, anywhere else it stands for itself
+        if index + 2 < allowed.len() && allowed[index + 1] == '-' {
+            if (allowed[index]..=allowed[index + 2]).contains(&character) {
+                return true;
+            }
+            index += 3;
+        } else {
+            if allowed[index] == character {
+                return true;
+            }
+            index += 1;
+        }
+    }
+    false
+}
+
+/// Normalizes a new short id and checks whether it may be used
+pub fn validate(short: &str, config: &ShortIdConfig) -> Result<String, String> {
+    let short = normalize(short, config);
+    if short.is_empty() {
+        return Err("The short id may not be empty".to_string());
+    }
+    if short.chars().count() > MAX_LENGTH {
+        return Err(format!(
+            "The short id may not exceed {MAX_LENGTH} characters"
+        ));
+    }
+    if let Some(character) = short
+        .chars()
+        .find(|character| !is_allowed(*character, &config.allowed_characters))
+    {
+        return Err(format!(
+            "The short id may not contain {character:?}, allowed are `{}`",
+            config.allowed_characters
+        ));
+    }
+    // The `/api` scope takes precedence over short URLs
+    let lowercase = short.to_lowercase();
+    if lowercase == "api"
+        || config
+            .reserved
+            .iter()
+            .any(|reserved| reserved.to_lowercase() == lowercase)
+    {
+        return Err(format!("The short id `{short}` is reserved"));
+    }
+    Ok(short)
+}
+
+/// Counts the characters which have to be inserted, removed or replaced to turn one short id into
+/// the other
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with(allowed_characters: &str, case_insensitive: bool) -> ShortIdConfig {
+        ShortIdConfig {
+            allowed_characters: allowed_characters.to_string(),
+            case_insensitive,
+            ..ShortIdConfig::default()
+        }
+    }
+
+    #[test]
+    fn is_allowed_parses_ranges() {
+        assert!(is_allowed('m', "a-z"));
+        assert!(is_allowed('Q', "a-zA-Z0-9"));
+        assert!(is_allowed('5', "a-zA-Z0-9"));
+        assert!(!is_allowed('-', "a-z"));
+        assert!(!is_allowed('_', "a-zA-Z0-9"));
+    }
+
+    #[test]
+    fn is_allowed_keeps_dashes_outside_ranges() {
+        assert!(is_allowed('-', "a-z_-"));
+        assert!(is_allowed('_', "a-z_-"));
+        assert!(is_allowed('-', "-a"));
+        assert!(!is_allowed('b', "-a"));
+    }
+
+    #[test]
+    fn is_allowed_supports_multibyte_characters() {
+        assert!(is_allowed('ü', "a-zäöüß"));
+        assert!(is_allowed('λ', "α-ω"));
+        assert!(!is_allowed('Ω', "α-ω"));
+    }
+
+    #[test]
+    fn normalize_composes_and_lowercases() {
+        // `u` followed by a combining diaeresis
+        let decomposed = "mu\u{308}nchen";
+        assert_eq!(normalize(decomposed, &config_with("a-z", false)), "münchen");
+        assert_eq!(normalize("MÜnchen", &config_with("a-z", false)), "MÜnchen");
+        assert_eq!(normalize("MÜnchen", &config_with("a-z", true)), "münchen");
+    }
+
+    #[test]
+    fn validate_checks_characters_and_length() {
+        let config = ShortIdConfig::default();
+        assert_eq!(validate("stand-up_2", &config).as_deref(), Ok("stand-up_2"));
+        assert!(validate("", &config).is_err());
+        assert!(validate("stand up", &config).is_err());
+        assert!(validate("münchen", &config).is_err());
+        assert!(validate(&"a".repeat(MAX_LENGTH), &config).is_ok());
+        assert!(validate(&"a".repeat(MAX_LENGTH + 1), &config).is_err());
+        assert_eq!(
+            validate("mu\u{308}nchen", &config_with("a-zü", false)).as_deref(),
+            Ok("münchen")
+        );
+    }
+
+    #[test]
+    fn validate_rejects_reserved_words() {
+        let config = ShortIdConfig {
+            reserved: vec!["Admin".to_string()],
+            ..ShortIdConfig::default()
+        };
+        assert!(validate("admin", &config).is_err());
+        assert!(validate("ADMIN", &config).is_err());
+        assert!(validate("admins", &config).is_ok());
+        // `api` stays reserved even if it is missing from the configuration
+        assert!(validate("api", &config).is_err());
+        assert!(validate("Api", &config).is_err());
+    }
+
+    #[test]
+    fn edit_distance_counts_changes() {
+        assert_eq!(edit_distance("standup", "standup"), 0);
+        assert_eq!(edit_distance("standup", "stand-up"), 1);
+        assert_eq!(edit_distance("standup", "standpu"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("münchen", "munchen"), 1);
+    }
+}